@@ -1,6 +1,14 @@
+use std::path::PathBuf;
+
+use client::StrategyKind;
+
+use crate::simulate;
+
 pub(crate) enum Args {
     Server,
-    Client,
+    /// Play from the terminal, or let a bot play.
+    Client(ClientOptions),
+    Simulate(simulate::Options),
 }
 
 pub(crate) fn parse_args() -> anyhow::Result<Args> {
@@ -8,9 +16,69 @@ pub(crate) fn parse_args() -> anyhow::Result<Args> {
 
     match pargs.subcommand()?.as_deref() {
         Some("server") => Ok(Args::Server),
-        Some("client") => Ok(Args::Client),
+        Some("client") => parse_client(&mut pargs).map(Args::Client),
+        Some("simulate") => parse_simulate(&mut pargs).map(Args::Simulate),
         _ => {
-            anyhow::bail!("must supply either 'server' or 'client'")
+            anyhow::bail!("must supply either 'server', 'client' or 'simulate'")
         }
     }
 }
+
+pub(crate) struct ClientOptions {
+    /// The server to connect to, and the name its TLS certificate has to be for.
+    pub host: String,
+    /// The port it listens on, the server's default if not given.
+    pub port: u16,
+    /// Let a bot play, rather than the person at the terminal.
+    pub strategy: Option<StrategyKind>,
+    /// Connect over TLS.
+    pub tls: bool,
+    /// Trust the certificate authority in this file, rather than the system's.
+    pub ca: Option<PathBuf>,
+}
+
+/// `client [--host example.com] [--port 1234] [--strategy greedy] [--tls] [--ca ca.pem]`
+fn parse_client(pargs: &mut pico_args::Arguments) -> anyhow::Result<ClientOptions> {
+    let host = pargs
+        .opt_value_from_str("--host")?
+        .unwrap_or_else(|| "localhost".to_owned());
+    let port = pargs
+        .opt_value_from_str("--port")?
+        .unwrap_or_else(server::config::defaults::port);
+    let strategy = pargs.opt_value_from_str("--strategy")?;
+    let tls = pargs.contains("--tls");
+    let ca = pargs.opt_value_from_str("--ca")?;
+
+    Ok(ClientOptions {
+        host,
+        port,
+        strategy,
+        // a certificate authority is only any use over TLS
+        tls: tls || ca.is_some(),
+        ca,
+    })
+}
+
+/// `simulate [--games N] [--players P] [--strategy greedy,random] [--rules preset] [--seed S]`
+fn parse_simulate(pargs: &mut pico_args::Arguments) -> anyhow::Result<simulate::Options> {
+    let bots = match pargs.opt_value_from_str::<_, String>("--strategy")? {
+        Some(names) => names
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?,
+        None => StrategyKind::ALL.to_vec(),
+    };
+
+    let players = pargs.opt_value_from_str("--players")?.unwrap_or(4);
+    if !(server::config::MIN_PLAYER_COUNT..=server::config::MAX_PLAYER_COUNT).contains(&players) {
+        anyhow::bail!("can't play with {players} players");
+    }
+
+    Ok(simulate::Options {
+        games: pargs.opt_value_from_str("--games")?.unwrap_or(1000),
+        players,
+        bots,
+        rules: pargs.opt_value_from_str("--rules")?.unwrap_or_default(),
+        seed: pargs.opt_value_from_str("--seed")?.unwrap_or(0),
+    })
+}
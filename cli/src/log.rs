@@ -4,6 +4,8 @@ use tracing_subscriber::{fmt, EnvFilter};
 pub(crate) fn init() -> anyhow::Result<()> {
     fmt()
         .compact()
+        // keep stdout clear for the terminal UI
+        .with_writer(std::io::stderr)
         .with_env_filter(
             EnvFilter::builder()
                 .with_default_directive(if cfg!(debug_assertions) {
@@ -11,7 +13,9 @@ pub(crate) fn init() -> anyhow::Result<()> {
                 } else {
                     LevelFilter::ERROR.into()
                 })
-                .from_env_lossy(),
+                .from_env_lossy()
+                // every TLS handshake is pages of traces
+                .add_directive("rustls=info".parse()?),
         )
         .try_init()
         .map_err(|e| anyhow::anyhow!(e))?;
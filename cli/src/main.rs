@@ -1,27 +1,34 @@
 mod cli;
 mod log;
+mod simulate;
 
-use std::net::IpAddr;
-
-use client::GameClient;
+use client::{GameClient, Tls};
 use server::{self, GameServer};
 use tokio::{select, task};
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 
-async fn start_client() -> anyhow::Result<()> {
+async fn start_client(options: cli::ClientOptions) -> anyhow::Result<()> {
     let token = CancellationToken::new();
 
+    let tls = if options.tls {
+        Some(Tls::new(&options.host, options.ca.as_deref())?)
+    } else {
+        None
+    };
+
     let client_task = {
         let token = token.child_token();
 
-        let addr = (
-            "127.0.0.1".parse::<IpAddr>().unwrap(),
-            server::config::defaults::port(),
-        );
+        let addr = (options.host, options.port);
 
         task::spawn(async move {
-            let client = GameClient::connect(addr).await;
+            let client = match options.strategy {
+                Some(strategy) => {
+                    GameClient::connect(addr, tls, strategy.build(rand::random())).await
+                }
+                None => GameClient::connect_interactive(addr, tls).await,
+            };
             client.start(token).await;
         })
     };
@@ -52,12 +59,12 @@ async fn start_client() -> anyhow::Result<()> {
 async fn start_server() -> anyhow::Result<()> {
     let token = CancellationToken::new();
 
+    let server = GameServer::from_config()?;
+
     let server_task = {
         let token = token.child_token();
 
         task::spawn(async move {
-            let server = GameServer::from_config();
-
             server.run(token).await;
         })
     };
@@ -91,7 +98,8 @@ async fn main() -> anyhow::Result<()> {
 
     match cli::parse_args()? {
         cli::Args::Server => start_server().await?,
-        cli::Args::Client => start_client().await?,
+        cli::Args::Client(options) => start_client(options).await?,
+        cli::Args::Simulate(options) => print!("{}", simulate::run(&options)),
     }
 
     Ok(())
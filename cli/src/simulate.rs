@@ -0,0 +1,172 @@
+//! Bots playing against each other on the engine directly, no networking involved.
+//!
+//! Used to see how house rules change the game before playing with them.
+
+use std::{fmt, time::Instant};
+
+use client::StrategyKind;
+use common::rules::{Preset, RuleSet};
+use game::{Event, Game, Timings};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use server::bots::Bots;
+
+/// Give up on a game that's taken this many steps, the bots might never call Cambio.
+const MAX_STEPS: usize = 100_000;
+
+pub(crate) struct Options {
+    pub games: usize,
+    pub players: usize,
+    /// Bots sit in this order, repeating if there are more players than bots.
+    pub bots: Vec<StrategyKind>,
+    pub rules: Preset,
+    pub seed: u64,
+}
+
+/// What happened over all of the games.
+#[derive(Default)]
+pub(crate) struct Report {
+    games: usize,
+    abandoned: usize,
+    rounds: usize,
+    turns: usize,
+    cambio_calls: usize,
+    cambio_wins: usize,
+    /// Matches won, by seat.
+    wins: Vec<usize>,
+    bots: Vec<StrategyKind>,
+    round_scores: Vec<i32>,
+}
+
+pub(crate) fn run(options: &Options) -> Report {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut report = Report {
+        wins: vec![0; options.players],
+        bots: (0..options.players)
+            .map(|seat| options.bots[seat % options.bots.len()])
+            .collect(),
+        ..Report::default()
+    };
+
+    for _ in 0..options.games {
+        play(options.rules.rules(), &mut rng, &mut report);
+    }
+
+    report
+}
+
+fn play(rules: RuleSet, rng: &mut StdRng, report: &mut Report) {
+    let mut game = Game::new_with_seed(rules, Timings::default(), rng.gen());
+    let mut bots = Bots::new();
+    for &kind in &report.bots {
+        bots.add(kind, rng.gen());
+    }
+    let ids = bots.ids().collect::<Vec<_>>();
+    for &id in &ids {
+        game.join(id);
+    }
+
+    report.games += 1;
+
+    let mut now = Instant::now();
+    let mut caller = None;
+
+    for _ in 0..MAX_STEPS {
+        let Some(event) = game.poll_events() else {
+            if let Some(deadline) = game.poll_wait_deadline() {
+                now = now.max(deadline);
+            }
+            game.advance(now);
+            continue;
+        };
+
+        bots.act(&mut game, &event, now);
+
+        match event {
+            Event::StartRound(_) => {
+                report.rounds += 1;
+                caller = None;
+            }
+            Event::StartTurn(_) => report.turns += 1,
+            Event::Cambio(id) => {
+                caller = Some(id);
+                report.cambio_calls += 1;
+            }
+            Event::WaitForSnap => {
+                // nobody else is going to snap
+                if let Some(deadline) = game.poll_wait_deadline() {
+                    now = now.max(deadline);
+                }
+            }
+            Event::FindWinner(winner) if caller == Some(winner) => report.cambio_wins += 1,
+            Event::RoundScores(standings) => {
+                report
+                    .round_scores
+                    .extend(standings.iter().filter(|s| !s.eliminated).map(|s| s.round));
+            }
+            Event::MatchResult(winner, _) => {
+                let seat = ids.iter().position(|&id| id == winner).unwrap();
+                report.wins[seat] += 1;
+                return;
+            }
+            _ => (),
+        }
+    }
+
+    report.abandoned += 1;
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per = |count: usize, of: usize| count as f64 / of.max(1) as f64;
+
+        writeln!(f, "games:              {}", self.games)?;
+        if self.abandoned > 0 {
+            writeln!(f, "abandoned:          {}", self.abandoned)?;
+        }
+        writeln!(f, "rounds per game:    {:.2}", per(self.rounds, self.games))?;
+        writeln!(f, "turns per round:    {:.2}", per(self.turns, self.rounds))?;
+        writeln!(
+            f,
+            "cambio calls:       {} ({:.1}% won by the caller)",
+            self.cambio_calls,
+            100.0 * per(self.cambio_wins, self.cambio_calls)
+        )?;
+
+        let mut scores = self.round_scores.clone();
+        scores.sort_unstable();
+        if let (Some(min), Some(max)) = (scores.first(), scores.last()) {
+            let mean = scores.iter().map(|&s| s as f64).sum::<f64>() / scores.len() as f64;
+            let median = scores[scores.len() / 2];
+            writeln!(
+                f,
+                "round scores:       min {min}, median {median}, mean {mean:.2}, max {max}"
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "seat  strategy  wins  win rate")?;
+        for (seat, (bot, wins)) in self.bots.iter().zip(&self.wins).enumerate() {
+            let rate = 100.0 * per(*wins, self.games);
+            writeln!(f, "{seat:<4}  {bot:<8}  {wins:<4}  {rate:.1}%")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn every_game_finishes() {
+    let options = Options {
+        games: 20,
+        players: 4,
+        bots: StrategyKind::ALL.to_vec(),
+        rules: Preset::Standard,
+        seed: 1,
+    };
+    let report = run(&options);
+
+    assert_eq!(report.games, 20);
+    assert_eq!(report.abandoned, 0);
+    assert_eq!(report.wins.iter().sum::<usize>(), 20);
+    assert!(report.cambio_calls > 0);
+}
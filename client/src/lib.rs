@@ -1,78 +1,262 @@
+use std::{
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
 use common::{
+    decisions::Slot,
     event::{
+        self,
         client::{self, Event},
-        server,
+        server, Capability, Heartbeat,
     },
-    stream,
+    stream::{self, Format},
+    Card,
 };
 use futures::prelude::*;
 use tokio::{
     net::{TcpStream, ToSocketAddrs},
-    select,
+    select, time,
 };
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+pub mod strategy;
+mod table;
+mod tls;
+mod tui;
+
+pub use strategy::{Strategy, StrategyKind};
+pub use table::{Change, Phase, TableState};
+pub use tls::Tls;
+
+/// Times to try getting back into the game after the connection drops.
+const RECONNECT_ATTEMPTS: usize = 5;
+/// Time to wait before each attempt to reconnect.
+const RECONNECT_WAIT: Duration = Duration::from_secs(2);
+
+/// How we introduce ourselves to the server.
+const CLIENT_NAME: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Who the server knows a player as, so they can rejoin if they drop out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub id: Uuid,
+    pub token: Uuid,
+}
+
+/// Why a game loop stopped.
+pub(crate) enum Ended {
+    /// The player left, or the server closed.
+    Left,
+    /// The connection was lost.
+    Dropped,
+}
 
 pub struct GameClient {
+    addr: SocketAddr,
+    /// Connects over TLS if set.
+    tls: Option<Tls>,
     read: stream::Read<server::Event>,
     write: stream::Write<client::Event>,
+    session: Option<Session>,
+    /// What the server said it supports when we last connected.
+    capabilities: Vec<Capability>,
+    /// How often the server said it pings.
+    heartbeat: Option<Heartbeat>,
+    /// Plays on its own if set, otherwise the person at the terminal does.
+    strategy: Option<Box<dyn Strategy>>,
+    screen: Option<tui::Screen>,
 }
 
 impl GameClient {
-    pub async fn connect<A: ToSocketAddrs>(addr: A) -> Self {
-        let stream = TcpStream::connect(addr).await.unwrap();
+    /// Connect a bot, playing with `strategy`.
+    pub async fn connect<A: ToSocketAddrs>(
+        addr: A,
+        tls: Option<Tls>,
+        strategy: Box<dyn Strategy>,
+    ) -> Self {
+        Self::connect_with(addr, tls, Some(strategy)).await
+    }
+
+    /// Connect someone playing from the terminal.
+    pub async fn connect_interactive<A: ToSocketAddrs>(addr: A, tls: Option<Tls>) -> Self {
+        Self::connect_with(addr, tls, None).await
+    }
+
+    async fn connect_with<A: ToSocketAddrs>(
+        addr: A,
+        tls: Option<Tls>,
+        strategy: Option<Box<dyn Strategy>>,
+    ) -> Self {
+        let (addr, read, write) = open(addr, tls.as_ref())
+            .await
+            .expect("failed to connect to server");
 
-        let (read, write) = stream::split(stream);
+        Self {
+            addr,
+            tls,
+            read,
+            write,
+            session: None,
+            capabilities: Vec::new(),
+            heartbeat: None,
+            strategy,
+            screen: None,
+        }
+    }
 
-        Self { read, write }
+    /// The id and token the server gave us, once we've joined.
+    pub fn session(&self) -> Option<Session> {
+        self.session
     }
 
     pub async fn start(mut self, token: CancellationToken) {
         select! {
-            _ = self.game_loop() => {}
+            _ = self.play() => {}
             _ = token.cancelled() => {
                 info!("leaving server");
-                self.write.send(client::Event::Leave).await.unwrap();
+                let _ = self.write.send(client::Event::Leave).await;
             }
         }
     }
 
-    async fn game_loop(&mut self) {
-        self.write
-            .send(Event::Join(client::Join::New))
-            .await
-            .unwrap();
+    /// Play until we leave, rejoining whenever the connection drops.
+    async fn play(&mut self) {
+        while let Ended::Dropped = self.game_loop().await {
+            if !self.capabilities.contains(&Capability::Rejoin) {
+                error!("server doesn't keep seats for players who drop out, giving up");
+                break;
+            }
+            if !self.reconnect().await {
+                error!("couldn't reconnect, giving up");
+                break;
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) -> bool {
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            time::sleep(RECONNECT_WAIT).await;
+            info!(attempt, "reconnecting");
+
+            match open(self.addr, self.tls.as_ref()).await {
+                Ok((_, read, write)) => {
+                    // back to JSON until the server says otherwise
+                    (self.read, self.write) = (read, write);
+                    return true;
+                }
+                Err(e) => warn!("failed to reconnect: {e}"),
+            }
+        }
+
+        false
+    }
+
+    /// Check the server speaks our protocol, and find out what it supports.
+    async fn hello(&mut self) -> Result<(), Ended> {
+        let hello = Event::Hello {
+            protocol_version: event::PROTOCOL_VERSION,
+            client_name: CLIENT_NAME.to_owned(),
+            capabilities: Capability::ALL.to_vec(),
+            formats: Format::ALL.to_vec(),
+        };
+        if self.write.send(hello).await.is_err() {
+            return Err(Ended::Dropped);
+        }
+
+        match self.read.try_next().await {
+            Ok(Some(server::Event::Hello {
+                protocol_version,
+                capabilities,
+                format,
+                heartbeat,
+            })) => {
+                info!(
+                    protocol_version,
+                    ?capabilities,
+                    ?format,
+                    "server said hello"
+                );
+                self.capabilities = capabilities;
+                self.heartbeat = heartbeat;
+                self.read.set_format(format);
+                self.write.set_format(format);
+                Ok(())
+            }
+            Ok(Some(server::Event::IncompatibleVersion {
+                min_supported,
+                max_supported,
+                ..
+            })) => {
+                error!(
+                    "server only supports protocol versions {min_supported} to {max_supported}, \
+                     we speak {}",
+                    event::PROTOCOL_VERSION
+                );
+                Err(Ended::Left)
+            }
+            read => {
+                error!("never received hello: {read:?}");
+                Err(Ended::Left)
+            }
+        }
+    }
+
+    async fn game_loop(&mut self) -> Ended {
+        if let Err(ended) = self.hello().await {
+            return ended;
+        }
 
-        let Some(server::Event::AssignId { id }) = self.read.try_next().await.unwrap() else {
+        let join = match self.session {
+            Some(Session { id, token }) => client::Join::Existing { id, token },
+            None => client::Join::New,
+        };
+        if self.write.send(Event::Join(join)).await.is_err() {
+            return Ended::Dropped;
+        }
+
+        let Ok(Some(server::Event::AssignId { id, token })) = self.read.try_next().await else {
             error!("failed to get ID");
-            return;
+            return Ended::Left;
         };
+        self.session = Some(Session { id, token });
 
-        let read = self.read.try_next().await.unwrap();
-        let Some(server::Event::Enter) = read else {
+        let read = self.read.try_next().await;
+        let Ok(Some(server::Event::Enter)) = read else {
             error!("never received enter: {read:?}");
-            return;
+            return Ended::Left;
         };
 
         info!("entering event loop");
 
+        let Some(strategy) = self.strategy.as_mut() else {
+            let screen = self.screen.get_or_insert_with(|| tui::Screen::new(id));
+            return screen
+                .play(&mut self.read, &mut self.write, self.heartbeat)
+                .await;
+        };
+
         async fn request_lobby_info(writer: &mut stream::Write<client::Event>) {
-            writer.send(Event::GetLobbyInfo).await.unwrap();
+            let _ = writer.send(Event::GetLobbyInfo).await;
         }
 
         request_lobby_info(&mut self.write).await;
 
-        let mut turn = id;
-        let mut card_in_hand = None;
+        let mut table = TableState::new(id);
+        let mut watchdog = Watchdog::new(self.heartbeat);
 
-        while let Some(msg) = self.read.try_next().await.unwrap() {
+        loop {
+            let Some(msg) = watchdog.next(&mut self.read).await else {
+                return Ended::Dropped;
+            };
             println!("GOT: {:?}", msg);
 
             match msg {
-                server::Event::LobbyInfo { player_count } => {
-                    if player_count >= 2 {
-                        let _ = self.write.send(client::Event::Start).await;
-                    }
+                server::Event::LobbyInfo { player_count } if player_count >= 2 => {
+                    let _ = self.write.send(client::Event::Start).await;
                 }
                 server::Event::Joined { id: _ } => {
                     request_lobby_info(&mut self.write).await;
@@ -80,34 +264,118 @@ impl GameClient {
                 server::Event::Left { id: _ } => {
                     request_lobby_info(&mut self.write).await;
                 }
-                server::Event::TurnStart { id, .. } => {
-                    turn = id;
+                server::Event::ServerClosing => {
+                    return Ended::Left;
                 }
-                server::Event::DrawCard(card) if turn == id => {
-                    card_in_hand = Some(card);
+                server::Event::Ping => {
+                    let _ = self.write.send(client::Event::Pong).await;
                 }
-                server::Event::WaitingForDecision if turn == id => {
-                    if let Some(card) = card_in_hand.take() {
-                        let valid_decisions = common::decisions::valid_set(card).into_vec();
-                        // TODO: let the user choose from the vector
-                        let decision = *valid_decisions.first().unwrap();
-                        self.write
-                            .send(client::Event::Decision(decision))
-                            .await
-                            .unwrap();
+                _ => (),
+            }
+
+            table.apply(&msg, Instant::now());
+
+            while let Some(change) = table.poll_changes() {
+                let reply = match change {
+                    Change::RoundStarted(_) => {
+                        strategy.on_new_round();
+                        None
+                    }
+                    Change::Phase(Phase::Deciding { id: turn }) if turn == id => table
+                        .drawn()
+                        .map(|card| decide(strategy.as_mut(), &table, card)),
+                    Change::Phase(Phase::ChoosingSwap { id: turn }) if turn == id => {
+                        table.swap_targets().map(|(own, other)| {
+                            let swap =
+                                strategy.choose_swap(table.view(), table.rules(), own, other);
+                            client::Event::ChooseSwap(swap)
+                        })
+                    }
+                    Change::Phase(Phase::Snapping) => {
+                        strategy
+                            .should_snap(table.view())
+                            .map(|index| client::Event::Snap {
+                                slot: Slot { player: id, index },
+                                give: None,
+                            })
+                    }
+                    Change::Phase(Phase::NewRound) => Some(client::Event::ConfirmNewRound),
+                    _ => None,
+                };
+
+                if let Some(event) = reply {
+                    if self.write.send(event).await.is_err() {
+                        return Ended::Dropped;
                     }
                 }
-                server::Event::WaitingForSnap if turn != id => {
-                    self.write.send(client::Event::Snap).await.unwrap();
-                }
-                server::Event::ConfirmNewRound => {
-                    self.write.send(client::Event::ConfirmNewRound).await.unwrap();
-                }
-                server::Event::ServerClosing => {
-                    break;
-                }
-                _ => (),
             }
         }
     }
 }
+
+/// Notices when the server goes quiet for longer than its heartbeat allows.
+pub(crate) struct Watchdog {
+    limit: Option<Duration>,
+    deadline: time::Instant,
+}
+
+impl Watchdog {
+    pub(crate) fn new(heartbeat: Option<Heartbeat>) -> Self {
+        let limit = heartbeat.map(|heartbeat| heartbeat.limit());
+        Self {
+            limit,
+            deadline: time::Instant::now() + limit.unwrap_or_default(),
+        }
+    }
+
+    /// The next event from the server, `None` if the connection dropped or went quiet.
+    pub(crate) async fn next(
+        &mut self,
+        read: &mut stream::Read<server::Event>,
+    ) -> Option<server::Event> {
+        let event = match self.limit {
+            Some(limit) => {
+                let Ok(event) = time::timeout_at(self.deadline, read.try_next()).await else {
+                    warn!("server has been quiet for {limit:?}, dropping the connection");
+                    return None;
+                };
+                self.deadline = time::Instant::now() + limit;
+                event
+            }
+            None => read.try_next().await,
+        };
+
+        event.ok().flatten()
+    }
+}
+
+/// Connect to the server at `addr`, over TLS if there's `tls`.
+async fn open<A: ToSocketAddrs>(
+    addr: A,
+    tls: Option<&Tls>,
+) -> io::Result<(
+    SocketAddr,
+    stream::Read<server::Event>,
+    stream::Write<client::Event>,
+)> {
+    let stream = TcpStream::connect(addr).await?;
+    let addr = stream.peer_addr()?;
+
+    let (read, write) = match tls {
+        Some(tls) => stream::split(tls.connect(stream).await?),
+        None => stream::split(stream),
+    };
+
+    Ok((addr, read, write))
+}
+
+/// What a bot does with the `card` it drew.
+fn decide(strategy: &mut dyn Strategy, table: &TableState, card: Card) -> client::Event {
+    if strategy.should_call_cambio(table.view(), table.rules()) {
+        return client::Event::CallCambio;
+    }
+
+    let valid = common::decisions::valid_set(card, table.rules()).into_vec();
+    let decision = strategy.choose_decision(table.view(), table.rules(), card, &valid);
+    client::Event::Decision(decision)
+}
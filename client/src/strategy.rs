@@ -0,0 +1,568 @@
+//! Ways for a bot to play.
+//!
+//! A [`Strategy`] is asked what to do whenever it's the bot's turn to act,
+//! and is shown the table through the bot's own [`PlayerView`],
+//! so it can only play with cards the bot has legitimately seen.
+
+use std::{fmt, str::FromStr};
+
+use common::{
+    data::{HandView, PlayerView},
+    decisions::{Decision, DecisionKind, Slot},
+    rules::RuleSet,
+    Card, FULL_DECK,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+
+/// How a bot decides what to do.
+pub trait Strategy: Send {
+    /// A new round has started, anything remembered about the last one is stale.
+    fn on_new_round(&mut self) {}
+
+    /// Pick what to do with the drawn `card`, using one of the `valid` kinds of decision.
+    fn choose_decision(
+        &mut self,
+        view: &PlayerView,
+        rules: &RuleSet,
+        card: Card,
+        valid: &[DecisionKind],
+    ) -> Decision;
+
+    /// Having looked at both cards, whether to swap `own` with `other`.
+    fn choose_swap(&mut self, view: &PlayerView, rules: &RuleSet, own: Slot, other: Slot) -> bool;
+
+    /// Which of the bot's own cards to snap, if any.
+    fn should_snap(&mut self, view: &PlayerView) -> Option<usize> {
+        known_match(view)
+    }
+
+    /// Asked at the start of each of the bot's turns.
+    fn should_call_cambio(&mut self, view: &PlayerView, rules: &RuleSet) -> bool;
+}
+
+/// The built in strategies, by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrategyKind {
+    /// Does anything it's allowed to.
+    Random,
+    /// Remembers its cards, keeps the low ones and calls Cambio early.
+    #[default]
+    Greedy,
+    /// Like greedy, but never swaps blind and only calls Cambio when sure.
+    Cautious,
+}
+
+impl StrategyKind {
+    pub const ALL: [StrategyKind; 3] = [
+        StrategyKind::Random,
+        StrategyKind::Greedy,
+        StrategyKind::Cautious,
+    ];
+
+    /// A new bot playing this way, `seed` makes any random choices repeatable.
+    pub fn build(self, seed: u64) -> Box<dyn Strategy> {
+        match self {
+            StrategyKind::Random => Box::new(Random::new(seed)),
+            StrategyKind::Greedy => Box::new(Greedy::new()),
+            StrategyKind::Cautious => Box::new(Cautious::new()),
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = serde::de::value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
+impl fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            StrategyKind::Random => "random",
+            StrategyKind::Greedy => "greedy",
+            StrategyKind::Cautious => "cautious",
+        })
+    }
+}
+
+/// A card the bot knows is in its hand and matches the top of the discard pile.
+pub fn known_match(view: &PlayerView) -> Option<usize> {
+    let top = view.top_discard?;
+    view.own_hand()?
+        .cards
+        .iter()
+        .position(|card| card.is_some_and(|card| card.same_rank(&top)))
+}
+
+fn value(card: Card, rules: &RuleSet) -> i32 {
+    card.game_value(rules) as i32
+}
+
+/// What a card nobody has seen is worth on average.
+fn expected_value(rules: &RuleSet) -> i32 {
+    let total = FULL_DECK
+        .iter()
+        .map(|&card| value(card, rules))
+        .sum::<i32>();
+    total / FULL_DECK.len() as i32
+}
+
+fn opponents(view: &PlayerView) -> impl Iterator<Item = &HandView> {
+    view.hands
+        .iter()
+        .filter(move |hand| hand.id != view.id && !hand.cards.is_empty())
+}
+
+/// Picks any of the decisions on offer, at any target.
+pub struct Random {
+    rng: StdRng,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for Random {
+    fn choose_decision(
+        &mut self,
+        view: &PlayerView,
+        _rules: &RuleSet,
+        _card: Card,
+        valid: &[DecisionKind],
+    ) -> Decision {
+        let own_len = view.own_hand().map_or(0, |hand| hand.cards.len());
+        let others = opponents(view).collect::<Vec<_>>();
+
+        let (Some(&kind), Some(other), true) = (
+            valid.choose(&mut self.rng),
+            others.choose(&mut self.rng),
+            own_len > 0,
+        ) else {
+            return Decision::Discard;
+        };
+        let own = self.rng.gen_range(0..own_len);
+        let other = Slot {
+            player: other.id,
+            index: self.rng.gen_range(0..other.cards.len()),
+        };
+
+        match kind {
+            DecisionKind::Discard => Decision::Discard,
+            DecisionKind::Replace => Decision::Replace { own },
+            DecisionKind::LookAtOwn => Decision::LookAtOwn { own },
+            DecisionKind::LookAtOther => Decision::LookAtOther { other },
+            DecisionKind::BlindSwap => Decision::BlindSwap { own, other },
+            DecisionKind::LookAndSwap => Decision::LookAndSwap { own, other },
+        }
+    }
+
+    fn choose_swap(&mut self, _: &PlayerView, _: &RuleSet, _: Slot, _: Slot) -> bool {
+        self.rng.gen()
+    }
+
+    fn should_call_cambio(&mut self, _view: &PlayerView, _rules: &RuleSet) -> bool {
+        self.rng.gen_bool(0.05)
+    }
+}
+
+/// Keeps its hand as low as it can with what it has seen,
+/// and calls Cambio as soon as its hand looks good.
+pub struct Greedy {
+    /// Call Cambio once the hand is expected to be worth this much or less.
+    call_at: i32,
+    /// Whether to swap cards without looking at them first.
+    blind_swaps: bool,
+    /// Turns taken this round, anyone who takes too long calls Cambio anyway.
+    turns: usize,
+}
+
+/// Turns to take in a round before calling Cambio no matter what.
+const PATIENCE: usize = 20;
+
+impl Greedy {
+    pub fn new() -> Self {
+        Self {
+            call_at: 10,
+            blind_swaps: true,
+            turns: 0,
+        }
+    }
+
+    /// The highest card the bot knows it has, or the first one it hasn't seen.
+    fn worst_own(view: &PlayerView, rules: &RuleSet) -> Option<(usize, Option<i32>)> {
+        let cards = &view.own_hand()?.cards;
+        let worst_known = cards
+            .iter()
+            .enumerate()
+            .filter_map(|(index, card)| Some((index, value((*card)?, rules))))
+            .max_by_key(|&(_, value)| value);
+        let unknown = cards.iter().position(Option::is_none);
+
+        match (worst_known, unknown) {
+            (Some((index, value)), _) if value > expected_value(rules) => {
+                Some((index, Some(value)))
+            }
+            (_, Some(index)) => Some((index, None)),
+            (known, None) => known.map(|(index, value)| (index, Some(value))),
+        }
+    }
+
+    /// The lowest card the bot knows about in someone else's hand.
+    fn best_other(view: &PlayerView, rules: &RuleSet) -> Option<(Slot, i32)> {
+        opponents(view)
+            .flat_map(|hand| {
+                hand.cards.iter().enumerate().filter_map(|(index, card)| {
+                    let slot = Slot {
+                        player: hand.id,
+                        index,
+                    };
+                    Some((slot, value((*card)?, rules)))
+                })
+            })
+            .min_by_key(|&(_, value)| value)
+    }
+
+    /// An opponent's card the bot hasn't seen.
+    fn unknown_other(view: &PlayerView) -> Option<Slot> {
+        opponents(view).find_map(|hand| {
+            let index = hand.cards.iter().position(Option::is_none)?;
+            Some(Slot {
+                player: hand.id,
+                index,
+            })
+        })
+    }
+
+    fn expected_hand(view: &PlayerView, rules: &RuleSet) -> Option<i32> {
+        let unknown = expected_value(rules);
+        let cards = &view.own_hand()?.cards;
+        Some(
+            cards
+                .iter()
+                .map(|card| card.map_or(unknown, |card| value(card, rules)))
+                .sum(),
+        )
+    }
+}
+
+impl Default for Greedy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Strategy for Greedy {
+    fn on_new_round(&mut self) {
+        self.turns = 0;
+    }
+
+    fn choose_decision(
+        &mut self,
+        view: &PlayerView,
+        rules: &RuleSet,
+        card: Card,
+        valid: &[DecisionKind],
+    ) -> Decision {
+        let allowed = |kind| valid.contains(&kind);
+        let Some((own, worst)) = Self::worst_own(view, rules) else {
+            return Decision::Discard;
+        };
+        let drawn = value(card, rules);
+        let unknown = expected_value(rules);
+
+        // keep the drawn card if it beats what it would replace
+        if drawn < worst.unwrap_or(unknown) {
+            return Decision::Replace { own };
+        }
+
+        // otherwise make the most of its power
+        let best_other = Self::best_other(view, rules);
+        if allowed(DecisionKind::LookAndSwap) {
+            if let Some(other) = best_other
+                .map(|(slot, _)| slot)
+                .or(Self::unknown_other(view))
+            {
+                return Decision::LookAndSwap { own, other };
+            }
+        }
+        if allowed(DecisionKind::BlindSwap) && self.blind_swaps {
+            if let Some((other, value)) = best_other {
+                if value < worst.unwrap_or(unknown) {
+                    return Decision::BlindSwap { own, other };
+                }
+            }
+        }
+        if allowed(DecisionKind::LookAtOwn) && worst.is_none() {
+            return Decision::LookAtOwn { own };
+        }
+        if allowed(DecisionKind::LookAtOther) {
+            if let Some(other) = Self::unknown_other(view) {
+                return Decision::LookAtOther { other };
+            }
+        }
+
+        Decision::Discard
+    }
+
+    fn choose_swap(&mut self, view: &PlayerView, rules: &RuleSet, own: Slot, other: Slot) -> bool {
+        let card_at = |slot: Slot| {
+            let hand = view.hands.iter().find(|hand| hand.id == slot.player)?;
+            hand.cards.get(slot.index).copied().flatten()
+        };
+
+        match (card_at(own), card_at(other)) {
+            (Some(own), Some(other)) => value(other, rules) < value(own, rules),
+            _ => false,
+        }
+    }
+
+    fn should_call_cambio(&mut self, view: &PlayerView, rules: &RuleSet) -> bool {
+        self.turns += 1;
+        self.turns > PATIENCE
+            || Self::expected_hand(view, rules).is_some_and(|total| total <= self.call_at)
+    }
+}
+
+/// Plays like [`Greedy`], but never swaps blind,
+/// and only calls Cambio once it has seen its whole hand and it's low.
+pub struct Cautious {
+    greedy: Greedy,
+}
+
+impl Cautious {
+    pub fn new() -> Self {
+        Self {
+            greedy: Greedy {
+                call_at: 5,
+                blind_swaps: false,
+                turns: 0,
+            },
+        }
+    }
+}
+
+impl Default for Cautious {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Strategy for Cautious {
+    fn on_new_round(&mut self) {
+        self.greedy.on_new_round();
+    }
+
+    fn choose_decision(
+        &mut self,
+        view: &PlayerView,
+        rules: &RuleSet,
+        card: Card,
+        valid: &[DecisionKind],
+    ) -> Decision {
+        self.greedy.choose_decision(view, rules, card, valid)
+    }
+
+    fn choose_swap(&mut self, view: &PlayerView, rules: &RuleSet, own: Slot, other: Slot) -> bool {
+        self.greedy.choose_swap(view, rules, own, other)
+    }
+
+    fn should_call_cambio(&mut self, view: &PlayerView, rules: &RuleSet) -> bool {
+        let seen_all = view
+            .own_hand()
+            .is_some_and(|hand| hand.cards.iter().all(Option::is_some));
+
+        // still gives up waiting eventually
+        self.greedy.should_call_cambio(view, rules) && (seen_all || self.greedy.turns > PATIENCE)
+    }
+}
+
+/// Us with `own` cards, and one opponent with `theirs`.
+#[cfg(test)]
+fn table(own: Vec<Option<Card>>, theirs: Vec<Option<Card>>, top: Option<Card>) -> PlayerView {
+    let (me, them) = (uuid::Uuid::from_u128(1), uuid::Uuid::from_u128(2));
+    PlayerView {
+        id: me,
+        hands: vec![
+            HandView { id: me, cards: own },
+            HandView {
+                id: them,
+                cards: theirs,
+            },
+        ],
+        top_discard: top,
+        deck_len: 30,
+    }
+}
+
+#[cfg(test)]
+fn spade(face: common::Face) -> Card {
+    Card::Normal {
+        suit: common::Suit::Spades,
+        face,
+    }
+}
+
+/// One of our own cards.
+#[cfg(test)]
+fn slot(view: &PlayerView, index: usize) -> Slot {
+    Slot {
+        player: view.id,
+        index,
+    }
+}
+
+#[test]
+fn random_only_picks_what_it_is_offered() {
+    use common::Face;
+
+    let rules = RuleSet::default();
+    let view = table(
+        vec![None; 4],
+        vec![None, Some(spade(Face::Ace))],
+        Some(spade(Face::Two)),
+    );
+    let valid = [DecisionKind::Discard, DecisionKind::LookAndSwap];
+
+    let mut bot = Random::new(7);
+    let (mut calls, mut swaps) = (0, 0);
+    for _ in 0..1000 {
+        let decision = bot.choose_decision(&view, &rules, spade(Face::Ten), &valid);
+        assert!(valid.contains(&decision.kind()));
+        if let Decision::LookAndSwap { own, other } = decision {
+            assert!(own < 4 && other.index < 2 && other.player != view.id);
+        }
+
+        calls += usize::from(bot.should_call_cambio(&view, &rules));
+        swaps += usize::from(bot.choose_swap(&view, &rules, slot(&view, 0), slot(&view, 0)));
+    }
+    // it calls now and then, and swaps about half the time
+    assert!((1..200).contains(&calls));
+    assert!((300..700).contains(&swaps));
+
+    // with nobody to target it just discards
+    let alone = table(vec![None; 4], vec![], None);
+    let decision = bot.choose_decision(&alone, &rules, spade(Face::Ten), &DecisionKind::ALL);
+    assert_eq!(decision, Decision::Discard);
+}
+
+#[test]
+fn greedy_keeps_low_cards_and_calls_early() {
+    use common::Face;
+
+    let rules = RuleSet::default();
+    let view = table(
+        vec![Some(spade(Face::Queen)), Some(spade(Face::Two))],
+        vec![None, Some(spade(Face::Ace))],
+        Some(spade(Face::Two)),
+    );
+    let them = Slot {
+        player: view.hands[1].id,
+        index: 1,
+    };
+    let mut bot = Greedy::new();
+
+    // a low card replaces the worst one it has
+    let decision = bot.choose_decision(
+        &view,
+        &rules,
+        spade(Face::Three),
+        &[DecisionKind::Discard, DecisionKind::Replace],
+    );
+    assert_eq!(decision, Decision::Replace { own: 0 });
+
+    // a high card is used to go after their ace
+    let look = [DecisionKind::Discard, DecisionKind::LookAndSwap];
+    let decision = bot.choose_decision(&view, &rules, spade(Face::King), &look);
+    assert_eq!(
+        decision,
+        Decision::LookAndSwap {
+            own: 0,
+            other: them
+        }
+    );
+    let blind = [DecisionKind::Discard, DecisionKind::BlindSwap];
+    let decision = bot.choose_decision(&view, &rules, spade(Face::King), &blind);
+    assert_eq!(
+        decision,
+        Decision::BlindSwap {
+            own: 0,
+            other: them
+        }
+    );
+
+    // only swaps for something it knows is lower
+    assert!(bot.choose_swap(&view, &rules, slot(&view, 0), them));
+    let unknown = Slot { index: 0, ..them };
+    assert!(!bot.choose_swap(&view, &rules, slot(&view, 1), unknown));
+
+    // snaps the two it knows it has
+    assert_eq!(bot.should_snap(&view), Some(1));
+    let no_match = PlayerView {
+        top_discard: Some(spade(Face::Five)),
+        ..view.clone()
+    };
+    assert_eq!(bot.should_snap(&no_match), None);
+
+    // a queen is too much to call with, a two and a three isn't
+    assert!(!bot.should_call_cambio(&view, &rules));
+    let low = table(
+        vec![Some(spade(Face::Two)), Some(spade(Face::Three))],
+        vec![None; 2],
+        None,
+    );
+    assert!(bot.should_call_cambio(&low, &rules));
+
+    // and it gives up waiting eventually
+    bot.on_new_round();
+    assert!((0..PATIENCE).all(|_| !bot.should_call_cambio(&view, &rules)));
+    assert!(bot.should_call_cambio(&view, &rules));
+}
+
+#[test]
+fn cautious_never_swaps_blind_or_calls_unsure() {
+    use common::Face;
+
+    let rules = RuleSet::default();
+    let view = table(
+        vec![Some(spade(Face::Queen)), Some(spade(Face::Two))],
+        vec![None, Some(spade(Face::Ace))],
+        None,
+    );
+    let mut bot = Cautious::new();
+
+    let blind = [DecisionKind::Discard, DecisionKind::BlindSwap];
+    let decision = bot.choose_decision(&view, &rules, spade(Face::King), &blind);
+    assert_eq!(decision, Decision::Discard);
+
+    // swaps like greedy once it has looked
+    let them = Slot {
+        player: view.hands[1].id,
+        index: 1,
+    };
+    assert!(bot.choose_swap(&view, &rules, slot(&view, 0), them));
+    assert_eq!(bot.should_snap(&view), None);
+
+    // a red king and an unseen card should be low, but it hasn't seen it
+    let red_king = Card::Normal {
+        suit: common::Suit::Hearts,
+        face: Face::King,
+    };
+    let unsure = table(vec![Some(red_king), None], vec![None; 2], None);
+    assert!(Greedy::new().should_call_cambio(&unsure, &rules));
+    assert!(!bot.should_call_cambio(&unsure, &rules));
+
+    let sure = table(
+        vec![Some(red_king), Some(spade(Face::Ace))],
+        vec![None; 2],
+        None,
+    );
+    assert!(bot.should_call_cambio(&sure, &rules));
+}
@@ -0,0 +1,604 @@
+//! The table as one player sees it, rebuilt from the events the server sends.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use common::{
+    data::{HandView, PlayerView, Standing},
+    decisions::{Decision, Slot},
+    event::server,
+    rules::RuleSet,
+    Card,
+};
+use uuid::Uuid;
+
+/// What's happening at the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting in the lobby for the host to start.
+    Lobby,
+    /// Cards are being dealt, or a turn is being played out.
+    Waiting,
+    /// `id` is deciding what to do with the card they drew.
+    Deciding { id: Uuid },
+    /// `id` is choosing whether to swap the two cards they looked at.
+    ChoosingSwap { id: Uuid },
+    /// Anyone can snap a card matching the discard pile.
+    Snapping,
+    /// Everyone's hand is on show at the end of the round.
+    ShowingAll,
+    /// Waiting for everyone to confirm another round.
+    NewRound,
+    /// The game has ended.
+    Over,
+}
+
+/// Something that changed at the table, in the order it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The number of players in the lobby.
+    Lobby {
+        players: usize,
+    },
+    /// The house rules were picked.
+    Rules,
+    /// We rejoined the game, anything could have changed.
+    Resynced,
+    RoundStarted(usize),
+    /// It's `id`'s turn.
+    Turn(Uuid),
+    Phase(Phase),
+    /// We drew a card.
+    Drew(Card),
+    /// Someone played their drawn card.
+    Played {
+        id: Uuid,
+        decision: Decision,
+    },
+    /// We've seen `card` in `slot`.
+    Seen {
+        slot: Slot,
+        card: Card,
+    },
+    Swapped(Slot, Slot),
+    Snapped {
+        id: Uuid,
+        slot: Slot,
+        card: Card,
+    },
+    /// Someone snapped a card that didn't match, everyone saw it.
+    WrongSnap {
+        id: Uuid,
+        slot: Slot,
+        card: Card,
+    },
+    /// Someone took a penalty card.
+    Penalty(Uuid),
+    /// A card moved from one hand to another, to fill a snapped slot.
+    Gave {
+        from: Slot,
+        to: Slot,
+    },
+    Discarded(Card),
+    /// The discard pile was shuffled back into the deck.
+    Reshuffled,
+    Cambio(Uuid),
+    RoundWon(Uuid),
+    /// Everyone's scores after a round, best first.
+    Scores(Vec<Standing>),
+    /// The match is over, `seed` can go in a bug report to replay it.
+    MatchWon {
+        winner: Uuid,
+        seed: u64,
+    },
+    /// The server turned down the last thing we sent.
+    Rejected(String),
+}
+
+/// Everything a player knows about the game, kept up to date by [`TableState::apply`].
+pub struct TableState {
+    id: Uuid,
+    view: PlayerView,
+    rules: RuleSet,
+    decision_time: Duration,
+    snap_time: Duration,
+    players: usize,
+    round: Option<usize>,
+    turn: Option<Uuid>,
+    drawn: Option<Card>,
+    swap: Option<(Slot, Slot)>,
+    standings: Vec<Standing>,
+    phase: Phase,
+    /// When whoever is being waited on runs out of time.
+    deadline: Option<Instant>,
+    changes: VecDeque<Change>,
+}
+
+impl TableState {
+    /// The table as the player `id` sees it, before anything has happened.
+    pub fn new(id: Uuid) -> Self {
+        Self {
+            id,
+            view: PlayerView {
+                id,
+                hands: Vec::new(),
+                top_discard: None,
+                deck_len: 0,
+            },
+            rules: RuleSet::default(),
+            decision_time: Duration::ZERO,
+            snap_time: Duration::ZERO,
+            players: 0,
+            round: None,
+            turn: None,
+            drawn: None,
+            swap: None,
+            standings: Vec::new(),
+            phase: Phase::Lobby,
+            deadline: None,
+            changes: VecDeque::new(),
+        }
+    }
+
+    /// Apply an `event` from the server, received at `now`.
+    pub fn apply(&mut self, event: &server::Event, now: Instant) {
+        match *event {
+            server::Event::LobbyInfo { player_count } => {
+                self.players = player_count;
+                self.change(Change::Lobby {
+                    players: player_count,
+                });
+            }
+            server::Event::Rules(ref rules) => {
+                self.rules = rules.clone();
+                self.change(Change::Rules);
+            }
+            server::Event::Timers { decision, snap } => {
+                self.decision_time = decision;
+                self.snap_time = snap;
+            }
+            server::Event::Resync(ref resync) => {
+                self.view = resync.view.clone();
+                self.round = resync.round;
+                self.turn = resync.turn;
+                self.drawn = None;
+                self.swap = None;
+                self.change(Change::Resynced);
+                self.set_phase(Phase::Waiting, None);
+            }
+            server::Event::Setup => self.set_phase(Phase::Waiting, None),
+            server::Event::FirstPeek(ref seen) => {
+                self.view = seen.clone();
+                for (index, card) in self.known_cards().collect::<Vec<_>>() {
+                    let slot = Slot {
+                        player: self.id,
+                        index,
+                    };
+                    self.change(Change::Seen { slot, card });
+                }
+            }
+            server::Event::RoundStart(round) => {
+                self.round = Some(round);
+                self.change(Change::RoundStarted(round));
+            }
+            server::Event::TurnStart { id } => {
+                self.turn = Some(id);
+                self.drawn = None;
+                self.swap = None;
+                // every turn starts by drawing, only the player drawing is told what
+                self.view.deck_len = self.view.deck_len.saturating_sub(1);
+                self.change(Change::Turn(id));
+                self.set_phase(Phase::Waiting, None);
+            }
+            server::Event::DrawCard(card) => {
+                self.drawn = Some(card);
+                self.change(Change::Drew(card));
+            }
+            server::Event::WaitingForDecision => {
+                if let Some(id) = self.turn {
+                    self.set_phase(Phase::Deciding { id }, Some(now + self.decision_time));
+                }
+            }
+            server::Event::PlayAction { id, decision } => {
+                match decision {
+                    Decision::Replace { own } => {
+                        // only whoever replaced it knows the new card
+                        let known = self.drawn.filter(|_| id == self.id);
+                        if let Some(cell) = self.cell(Slot {
+                            player: id,
+                            index: own,
+                        }) {
+                            *cell = known;
+                        }
+                    }
+                    Decision::LookAndSwap { own, other } => {
+                        self.swap = Some((
+                            Slot {
+                                player: id,
+                                index: own,
+                            },
+                            other,
+                        ));
+                    }
+                    _ => (),
+                }
+                self.change(Change::Played { id, decision });
+                self.set_phase(Phase::Waiting, None);
+            }
+            server::Event::Discard(card) => {
+                self.view.top_discard = Some(card);
+                self.change(Change::Discarded(card));
+            }
+            server::Event::Reshuffle => {
+                // everything but the hands and the top discard goes back in
+                let in_hands = self
+                    .view
+                    .hands
+                    .iter()
+                    .map(|hand| hand.cards.len())
+                    .sum::<usize>();
+                let deck_len = common::FULL_DECK.len().saturating_sub(in_hands + 1);
+                // a turn's draw was counted when it started, a penalty's is when it's given
+                let drawn = usize::from(self.phase != Phase::Snapping);
+                self.view.deck_len = deck_len.saturating_sub(drawn);
+                self.change(Change::Reshuffled);
+            }
+            server::Event::Peek { slot, card } => {
+                if let Some(cell) = self.cell(slot) {
+                    *cell = Some(card);
+                }
+                self.change(Change::Seen { slot, card });
+            }
+            server::Event::WaitingForSwapChoice => {
+                if let Some(id) = self.turn {
+                    let deadline = Some(now + self.decision_time);
+                    self.set_phase(Phase::ChoosingSwap { id }, deadline);
+                }
+            }
+            server::Event::Swapped(a, b) => {
+                let a_card = self.cell(a).and_then(Option::take);
+                let b_card = self
+                    .cell(b)
+                    .and_then(|cell| std::mem::replace(cell, a_card));
+                if let Some(cell) = self.cell(a) {
+                    *cell = b_card;
+                }
+                self.change(Change::Swapped(a, b));
+            }
+            server::Event::WaitingForSnap => {
+                self.set_phase(Phase::Snapping, Some(now + self.snap_time));
+            }
+            server::Event::Snapped { id, slot, card } => {
+                if let Some(hand) = self.hand_mut(slot.player) {
+                    if slot.index < hand.cards.len() {
+                        hand.cards.remove(slot.index);
+                    }
+                }
+                self.view.top_discard = Some(card);
+                self.change(Change::Snapped { id, slot, card });
+            }
+            server::Event::WrongSnap { id, slot, card } => {
+                if let Some(cell) = self.cell(slot) {
+                    *cell = Some(card);
+                }
+                // it was turned over for everyone
+                self.change(Change::Seen { slot, card });
+                self.change(Change::WrongSnap { id, slot, card });
+            }
+            server::Event::Penalty { id } => {
+                if let Some(hand) = self.hand_mut(id) {
+                    hand.cards.push(None);
+                }
+                self.view.deck_len = self.view.deck_len.saturating_sub(1);
+                self.change(Change::Penalty(id));
+            }
+            server::Event::GaveCard { from, to } => {
+                let given = self
+                    .hand_mut(from.player)
+                    .filter(|hand| from.index < hand.cards.len())
+                    .map(|hand| hand.cards.remove(from.index));
+                if let (Some(given), Some(hand)) = (given, self.hand_mut(to.player)) {
+                    let index = to.index.min(hand.cards.len());
+                    hand.cards.insert(index, given);
+                }
+                self.change(Change::Gave { from, to });
+            }
+            server::Event::Rejected { ref reason } => {
+                self.change(Change::Rejected(reason.clone()));
+            }
+            server::Event::EndTurn => self.set_phase(Phase::Waiting, None),
+            server::Event::CambioCall { id } => self.change(Change::Cambio(id)),
+            server::Event::ShowAll(ref seen) => {
+                self.view = seen.clone();
+                self.set_phase(Phase::ShowingAll, None);
+            }
+            server::Event::Winner { id } => self.change(Change::RoundWon(id)),
+            server::Event::RoundScores(ref standings) => {
+                self.standings = standings.clone();
+                self.change(Change::Scores(standings.clone()));
+            }
+            server::Event::MatchResult {
+                winner,
+                ref standings,
+                seed,
+            } => {
+                self.standings = standings.clone();
+                self.change(Change::MatchWon { winner, seed });
+            }
+            server::Event::ConfirmNewRound => self.set_phase(Phase::NewRound, None),
+            server::Event::GameEnd => self.set_phase(Phase::Over, None),
+            _ => (),
+        }
+    }
+
+    /// Pop off the next [`Change`], if there is any.
+    pub fn poll_changes(&mut self) -> Option<Change> {
+        self.changes.pop_front()
+    }
+
+    /// The player looking at the table.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Everything the player can see, cards they haven't seen are `None`.
+    pub fn view(&self) -> &PlayerView {
+        &self.view
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Number of players in the lobby, when last asked.
+    pub fn player_count(&self) -> usize {
+        self.players
+    }
+
+    pub fn round(&self) -> Option<usize> {
+        self.round
+    }
+
+    /// Whose turn it is.
+    pub fn turn(&self) -> Option<Uuid> {
+        self.turn
+    }
+
+    pub fn is_my_turn(&self) -> bool {
+        self.turn == Some(self.id)
+    }
+
+    /// The card we drew this turn.
+    pub fn drawn(&self) -> Option<Card> {
+        self.drawn
+    }
+
+    /// The cards looked at this turn, that could be swapped.
+    pub fn swap_targets(&self) -> Option<(Slot, Slot)> {
+        self.swap
+    }
+
+    /// The player's own hand.
+    pub fn my_hand(&self) -> Option<&HandView> {
+        self.view.own_hand()
+    }
+
+    /// The cards in our own hand we've seen, by index.
+    pub fn known_cards(&self) -> impl Iterator<Item = (usize, Card)> + '_ {
+        self.my_hand()
+            .into_iter()
+            .flat_map(|hand| hand.cards.iter().enumerate())
+            .filter_map(|(index, card)| Some((index, (*card)?)))
+    }
+
+    /// Everyone else at the table, in seat order.
+    pub fn opponents(&self) -> impl Iterator<Item = &HandView> {
+        self.view.hands.iter().filter(|hand| hand.id != self.id)
+    }
+
+    /// Scores from the last round played, best first.
+    pub fn standings(&self) -> &[Standing] {
+        &self.standings
+    }
+
+    /// How long whoever is being waited on has left, if there's a time limit.
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    fn change(&mut self, change: Change) {
+        self.changes.push_back(change);
+    }
+
+    fn set_phase(&mut self, phase: Phase, deadline: Option<Instant>) {
+        // the server repeats what it's waiting on, that doesn't give anyone more time
+        if self.phase != phase {
+            self.phase = phase;
+            self.deadline = deadline;
+            self.change(Change::Phase(phase));
+        }
+    }
+
+    fn hand_mut(&mut self, id: Uuid) -> Option<&mut HandView> {
+        self.view.hands.iter_mut().find(|hand| hand.id == id)
+    }
+
+    fn cell(&mut self, slot: Slot) -> Option<&mut Option<Card>> {
+        self.hand_mut(slot.player)?.cards.get_mut(slot.index)
+    }
+}
+
+#[test]
+fn follows_a_turn() {
+    let (me, them) = (Uuid::new_v4(), Uuid::new_v4());
+    let king = common::FULL_DECK[0];
+    let two = common::FULL_DECK[11];
+    let now = Instant::now();
+
+    let mut table = TableState::new(me);
+    let events = [
+        server::Event::Timers {
+            decision: Duration::from_secs(10),
+            snap: Duration::from_secs(5),
+        },
+        server::Event::FirstPeek(PlayerView {
+            id: me,
+            hands: vec![
+                HandView {
+                    id: me,
+                    cards: vec![Some(king), None],
+                },
+                HandView {
+                    id: them,
+                    cards: vec![None, None],
+                },
+            ],
+            top_discard: None,
+            deck_len: 10,
+        }),
+        server::Event::TurnStart { id: me },
+        server::Event::DrawCard(two),
+        server::Event::WaitingForDecision,
+    ];
+    for event in &events {
+        table.apply(event, now);
+    }
+
+    assert!(table.is_my_turn());
+    assert_eq!(table.phase(), Phase::Deciding { id: me });
+    assert_eq!(table.time_left(now), Some(Duration::from_secs(10)));
+    assert_eq!(table.known_cards().collect::<Vec<_>>(), [(0, king)]);
+    assert_eq!(table.view().deck_len, 9);
+
+    // replacing the king, we know what's there now
+    table.apply(
+        &server::Event::PlayAction {
+            id: me,
+            decision: Decision::Replace { own: 0 },
+        },
+        now,
+    );
+    table.apply(&server::Event::Discard(king), now);
+    assert_eq!(table.known_cards().collect::<Vec<_>>(), [(0, two)]);
+    assert_eq!(table.view().top_discard, Some(king));
+
+    // they took our two
+    let (ours, theirs) = (
+        Slot {
+            player: me,
+            index: 0,
+        },
+        Slot {
+            player: them,
+            index: 1,
+        },
+    );
+    table.apply(&server::Event::Swapped(ours, theirs), now);
+    assert_eq!(table.known_cards().count(), 0);
+    assert_eq!(table.opponents().next().unwrap().cards, [None, Some(two)]);
+
+    let changes = std::iter::from_fn(|| table.poll_changes()).collect::<Vec<_>>();
+    assert_eq!(
+        changes[0],
+        Change::Seen {
+            slot: Slot {
+                player: me,
+                index: 0
+            },
+            card: king
+        }
+    );
+    assert!(changes.contains(&Change::Phase(Phase::Deciding { id: me })));
+    assert_eq!(changes.last(), Some(&Change::Swapped(ours, theirs)));
+}
+
+#[test]
+fn waiting_again_keeps_counting_down() {
+    let (me, them) = (Uuid::new_v4(), Uuid::new_v4());
+    let start = Instant::now();
+
+    let mut table = TableState::new(me);
+    table.apply(
+        &server::Event::Timers {
+            decision: Duration::from_secs(10),
+            snap: Duration::from_secs(5),
+        },
+        start,
+    );
+    table.apply(&server::Event::TurnStart { id: them }, start);
+    table.apply(&server::Event::WaitingForDecision, start);
+
+    // someone else did something, and the server said it's still waiting
+    let later = start + Duration::from_secs(4);
+    table.apply(&server::Event::WaitingForDecision, later);
+    assert_eq!(table.time_left(later), Some(Duration::from_secs(6)));
+
+    // the next turn gets the full time again
+    table.apply(&server::Event::EndTurn, later);
+    table.apply(&server::Event::TurnStart { id: me }, later);
+    table.apply(&server::Event::WaitingForDecision, later);
+    assert_eq!(table.time_left(later), Some(Duration::from_secs(10)));
+}
+
+#[test]
+fn reshuffles_and_wrong_snaps() {
+    let (me, them) = (Uuid::new_v4(), Uuid::new_v4());
+    let four = common::FULL_DECK[3];
+    let now = Instant::now();
+
+    let mut table = TableState::new(me);
+    table.apply(
+        &server::Event::FirstPeek(PlayerView {
+            id: me,
+            hands: vec![
+                HandView {
+                    id: me,
+                    cards: vec![None; 4],
+                },
+                HandView {
+                    id: them,
+                    cards: vec![None; 4],
+                },
+            ],
+            top_discard: None,
+            deck_len: 0,
+        }),
+        now,
+    );
+
+    // the deck ran out as their turn started, everything else went back in and they drew
+    table.apply(&server::Event::TurnStart { id: them }, now);
+    table.apply(&server::Event::Reshuffle, now);
+    assert_eq!(table.view().deck_len, common::FULL_DECK.len() - 8 - 2);
+
+    // it ran out again when we snapped wrong, the penalty comes out of the new deck
+    let slot = Slot {
+        player: me,
+        index: 2,
+    };
+    let deck_len = common::FULL_DECK.len() - 8 - 1;
+    table.apply(&server::Event::WaitingForSnap, now);
+    table.apply(
+        &server::Event::WrongSnap {
+            id: me,
+            slot,
+            card: four,
+        },
+        now,
+    );
+    table.apply(&server::Event::Reshuffle, now);
+    table.apply(&server::Event::Penalty { id: me }, now);
+    assert_eq!(table.view().deck_len, deck_len - 1);
+
+    // everyone saw the card we turned over
+    assert_eq!(table.known_cards().collect::<Vec<_>>(), [(2, four)]);
+    let changes = std::iter::from_fn(|| table.poll_changes()).collect::<Vec<_>>();
+    assert!(changes.contains(&Change::Seen { slot, card: four }));
+}
@@ -0,0 +1,68 @@
+//! Securing the connection to the server with TLS.
+
+use std::{io, path::Path, sync::Arc};
+
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        crypto::ring,
+        pki_types::{pem::PemObject, CertificateDer, ServerName},
+        ClientConfig, RootCertStore,
+    },
+    TlsConnector,
+};
+
+/// Who the server has to prove it is, and who we trust to vouch for it.
+#[derive(Clone)]
+pub struct Tls {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl Tls {
+    /// Only talk to a server called `server_name`.
+    ///
+    /// Its certificate has to be signed by the certificate authority in the PEM file `ca`,
+    /// or by one the system trusts if there isn't one.
+    pub fn new(server_name: &str, ca: Option<&Path>) -> io::Result<Self> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+
+        let mut roots = RootCertStore::empty();
+        match ca {
+            Some(ca) => {
+                for cert in CertificateDer::pem_file_iter(ca).map_err(invalid)? {
+                    roots
+                        .add(cert.map_err(invalid)?)
+                        .map_err(io::Error::other)?;
+                }
+            }
+            None => {
+                let native = rustls_native_certs::load_native_certs();
+                roots.add_parsable_certificates(native.certs);
+            }
+        }
+
+        let server_name = ServerName::try_from(server_name.to_owned())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let provider = Arc::new(ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+
+    /// Secure a connection to the server, failing if it isn't who it should be.
+    pub async fn connect(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        self.connector
+            .connect(self.server_name.clone(), stream)
+            .await
+    }
+}
@@ -0,0 +1,596 @@
+//! Playing from the terminal.
+//!
+//! The table is redrawn after every event and key press,
+//! and once a second to count down the time left to act.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use common::{
+    data::HandView,
+    decisions::{valid_set, Decision, DecisionKind, Slot},
+    event::{client, server, Heartbeat},
+    stream, Card, Face, Suit,
+};
+use dialoguer::console::{style, Key, Term};
+use futures::prelude::*;
+use tokio::{select, sync::mpsc, time};
+use uuid::Uuid;
+
+use crate::{Change, Ended, Phase, TableState, Watchdog};
+
+/// Lines of history shown under the table.
+const LOG_LEN: usize = 8;
+
+/// The key for each decision, and what it's called.
+const DECISION_KEYS: [(char, DecisionKind, &str); 6] = [
+    ('d', DecisionKind::Discard, "discard"),
+    ('r', DecisionKind::Replace, "replace"),
+    ('l', DecisionKind::LookAtOwn, "look at own"),
+    ('o', DecisionKind::LookAtOther, "look at other"),
+    ('b', DecisionKind::BlindSwap, "blind swap"),
+    ('k', DecisionKind::LookAndSwap, "look and swap"),
+];
+
+/// What the player is being asked to do.
+#[derive(Clone, Copy)]
+enum Prompt {
+    /// Nothing, just watch.
+    Wait,
+    /// Waiting in the lobby for the host to start.
+    Lobby { players: usize },
+    /// Our turn, pick what to do with the drawn card.
+    Decide { card: Card },
+    /// Picking the cards a decision is played on.
+    Target {
+        card: Card,
+        kind: DecisionKind,
+        own: Option<usize>,
+        player: Option<Uuid>,
+    },
+    /// Having looked at both cards, swap them?
+    Swap,
+    /// Anyone can snap a card matching the discard pile.
+    Snap,
+    /// Play another round?
+    NewRound,
+}
+
+/// What to do after a key press.
+enum Input {
+    Send(client::Event),
+    Quit,
+    Nothing,
+}
+
+struct Table {
+    state: TableState,
+    prompt: Prompt,
+    /// What's happened recently, newest last.
+    log: VecDeque<String>,
+}
+
+/// The terminal, kept between connections so nothing is lost if we have to rejoin.
+pub(crate) struct Screen {
+    term: Term,
+    keys: mpsc::UnboundedReceiver<Key>,
+    table: Table,
+}
+
+impl Screen {
+    pub(crate) fn new(id: Uuid) -> Self {
+        let term = Term::stdout();
+        let _ = term.hide_cursor();
+
+        Self {
+            keys: keys(term.clone()),
+            term,
+            table: Table::new(id),
+        }
+    }
+
+    /// Play the game from the terminal, until the player quits or the connection ends.
+    ///
+    /// The connection counts as ended if the server goes quiet for longer than its `heartbeat`.
+    pub(crate) async fn play(
+        &mut self,
+        read: &mut stream::Read<server::Event>,
+        write: &mut stream::Write<client::Event>,
+        heartbeat: Option<Heartbeat>,
+    ) -> Ended {
+        let mut tick = time::interval(Duration::from_secs(1));
+        let mut watchdog = Watchdog::new(heartbeat);
+
+        let _ = write.send(client::Event::GetLobbyInfo).await;
+
+        let ended = loop {
+            let reply = select! {
+                msg = watchdog.next(read) => match msg {
+                    Some(server::Event::ServerClosing) => break Ended::Left,
+                    None => {
+                        self.table.say("lost connection, rejoining...".into());
+                        break Ended::Dropped;
+                    }
+                    Some(server::Event::Ping) => Some(client::Event::Pong),
+                    Some(event) => self.table.on_event(&event),
+                },
+                Some(key) = self.keys.recv() => match self.table.on_key(key) {
+                    Input::Send(event) => Some(event),
+                    Input::Quit => {
+                        let _ = write.send(client::Event::Leave).await;
+                        break Ended::Left;
+                    }
+                    Input::Nothing => None,
+                },
+                _ = tick.tick() => None,
+            };
+
+            if let Some(event) = reply {
+                if write.send(event).await.is_err() {
+                    break Ended::Dropped;
+                }
+            }
+
+            self.draw();
+        };
+
+        self.draw();
+        ended
+    }
+
+    fn draw(&self) {
+        let _ = self.term.clear_screen();
+        let _ = self.term.write_str(&self.table.draw());
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = self.term.show_cursor();
+    }
+}
+
+/// Read key presses on their own thread, reading blocks.
+fn keys(term: Term) -> mpsc::UnboundedReceiver<Key> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        while let Ok(key) = term.read_key() {
+            if tx.send(key).is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+impl Table {
+    fn new(id: Uuid) -> Self {
+        Self {
+            state: TableState::new(id),
+            prompt: Prompt::Lobby { players: 1 },
+            log: VecDeque::with_capacity(LOG_LEN),
+        }
+    }
+
+    fn id(&self) -> Uuid {
+        self.state.id()
+    }
+
+    fn say(&mut self, line: String) {
+        if self.log.len() == LOG_LEN {
+            self.log.pop_front();
+        }
+        self.log.push_back(line);
+    }
+
+    /// Update the table, returning anything to send back.
+    fn on_event(&mut self, event: &server::Event) -> Option<client::Event> {
+        self.state.apply(event, Instant::now());
+
+        while let Some(change) = self.state.poll_changes() {
+            self.on_change(change);
+        }
+
+        match event {
+            server::Event::Joined { .. } | server::Event::Left { .. } => {
+                Some(client::Event::GetLobbyInfo)
+            }
+            _ => None,
+        }
+    }
+
+    fn on_change(&mut self, change: Change) {
+        let id = self.id();
+
+        let line = match change {
+            Change::Lobby { players } => {
+                if let Prompt::Lobby { .. } = self.prompt {
+                    self.prompt = Prompt::Lobby { players };
+                }
+                return;
+            }
+            Change::Phase(phase) => {
+                self.prompt = match phase {
+                    Phase::Lobby => Prompt::Lobby {
+                        players: self.state.player_count(),
+                    },
+                    Phase::Deciding { id: turn } if turn == id => match self.state.drawn() {
+                        Some(card) => Prompt::Decide { card },
+                        None => Prompt::Wait,
+                    },
+                    Phase::ChoosingSwap { id: turn } if turn == id => Prompt::Swap,
+                    Phase::Snapping => Prompt::Snap,
+                    Phase::NewRound => Prompt::NewRound,
+                    Phase::Over => {
+                        self.say("the game is over".into());
+                        Prompt::Wait
+                    }
+                    _ => Prompt::Wait,
+                };
+                return;
+            }
+            Change::Resynced => "rejoined the game".into(),
+            Change::RoundStarted(round) => format!("round {} started", round + 1),
+            Change::Played { id, decision } => self.describe(id, decision),
+            Change::Seen { slot, card } => {
+                format!("you saw {} in {}", label(card), self.slot_name(slot))
+            }
+            Change::Swapped(a, b) => {
+                format!("{} and {} swapped", self.slot_name(a), self.slot_name(b))
+            }
+            Change::Snapped { id, card, .. } => {
+                format!("{} snapped {}", self.name(id), label(card))
+            }
+            Change::WrongSnap { id, card, .. } => {
+                format!(
+                    "{} snapped {}, which doesn't match",
+                    self.name(id),
+                    label(card)
+                )
+            }
+            Change::Penalty(id) => format!("{} drew a penalty card", self.name(id)),
+            Change::Gave { from, to } => {
+                format!("{} moved to {}", self.slot_name(from), self.slot_name(to))
+            }
+            Change::Reshuffled => "the discard pile was shuffled back in".into(),
+            Change::Rejected(reason) => format!("not allowed: {reason}"),
+            Change::Cambio(id) => format!("{} called Cambio!", self.name(id)),
+            Change::RoundWon(id) => format!("{} won the round", self.name(id)),
+            Change::Scores(standings) => {
+                let scores = standings
+                    .iter()
+                    .map(|s| format!("{} {} ({})", self.name(s.id), s.round, s.total))
+                    .collect::<Vec<_>>();
+                format!("scores: {}", scores.join(", "))
+            }
+            Change::MatchWon { winner, seed } => {
+                format!("{} won the match (game seed {seed})", self.name(winner))
+            }
+            _ => return,
+        };
+
+        self.say(line);
+    }
+
+    fn on_key(&mut self, key: Key) -> Input {
+        if key == Key::Char('q') {
+            return Input::Quit;
+        }
+        let digit = match key {
+            Key::Char(c) => c.to_digit(10).filter(|&n| n > 0).map(|n| n as usize - 1),
+            _ => None,
+        };
+
+        let event = match (self.prompt, key) {
+            (Prompt::Lobby { .. }, Key::Char('s')) => client::Event::Start,
+            (Prompt::Decide { .. }, Key::Char('c')) => client::Event::CallCambio,
+            (Prompt::Decide { card }, Key::Char(c)) => {
+                let valid = valid_set(card, self.state.rules());
+                let Some(&(_, kind, _)) = DECISION_KEYS
+                    .iter()
+                    .find(|&&(key, kind, _)| key == c && valid.contains(&kind))
+                else {
+                    return Input::Nothing;
+                };
+                if kind != DecisionKind::Discard {
+                    self.prompt = Prompt::Target {
+                        card,
+                        kind,
+                        own: None,
+                        player: None,
+                    };
+                    return Input::Nothing;
+                }
+                client::Event::Decision(Decision::Discard)
+            }
+            (Prompt::Target { card, .. }, Key::Escape) => {
+                self.prompt = Prompt::Decide { card };
+                return Input::Nothing;
+            }
+            (Prompt::Target { .. }, _) => match digit {
+                Some(n) => return self.pick_target(n),
+                None => return Input::Nothing,
+            },
+            (Prompt::Swap, Key::Char(c @ ('y' | 'n'))) => client::Event::ChooseSwap(c == 'y'),
+            (Prompt::Snap, _) if digit.is_some_and(|n| n < self.own_len()) => {
+                let slot = Slot {
+                    player: self.id(),
+                    index: digit.unwrap(),
+                };
+                client::Event::Snap { slot, give: None }
+            }
+            (Prompt::NewRound, Key::Char('y')) => client::Event::ConfirmNewRound,
+            (Prompt::NewRound, Key::Char('n')) => client::Event::SkipNewRound,
+            _ => return Input::Nothing,
+        };
+
+        if !matches!(self.prompt, Prompt::Lobby { .. }) {
+            self.prompt = Prompt::Wait;
+        }
+        Input::Send(event)
+    }
+
+    /// Pick the `n`th card or player, sending the decision once every target is picked.
+    fn pick_target(&mut self, n: usize) -> Input {
+        let Prompt::Target {
+            card,
+            kind,
+            mut own,
+            mut player,
+        } = self.prompt
+        else {
+            return Input::Nothing;
+        };
+        let mut other = None;
+
+        if needs_own(kind) && own.is_none() {
+            own = Some(n).filter(|&n| n < self.own_len());
+        } else if player.is_none() {
+            player = self.opponents().nth(n).map(|hand| hand.id);
+        } else if let Some(hand) = self.opponents().find(|hand| Some(hand.id) == player) {
+            other = Some(Slot {
+                player: hand.id,
+                index: n,
+            })
+            .filter(|_| n < hand.cards.len());
+        }
+
+        let decision = match (kind, own, other) {
+            (DecisionKind::Replace, Some(own), _) => Decision::Replace { own },
+            (DecisionKind::LookAtOwn, Some(own), _) => Decision::LookAtOwn { own },
+            (DecisionKind::LookAtOther, _, Some(other)) => Decision::LookAtOther { other },
+            (DecisionKind::BlindSwap, Some(own), Some(other)) => Decision::BlindSwap { own, other },
+            (DecisionKind::LookAndSwap, Some(own), Some(other)) => {
+                Decision::LookAndSwap { own, other }
+            }
+            _ => {
+                self.prompt = Prompt::Target {
+                    card,
+                    kind,
+                    own,
+                    player,
+                };
+                return Input::Nothing;
+            }
+        };
+
+        self.prompt = Prompt::Wait;
+        Input::Send(client::Event::Decision(decision))
+    }
+
+    fn own_len(&self) -> usize {
+        self.state.my_hand().map_or(0, |hand| hand.cards.len())
+    }
+
+    fn opponents(&self) -> impl Iterator<Item = &HandView> {
+        self.state.opponents()
+    }
+
+    /// How a player is shown, opponents are numbered in seat order.
+    fn name(&self, id: Uuid) -> String {
+        if id == self.id() {
+            return "you".to_owned();
+        }
+        match self.opponents().position(|hand| hand.id == id) {
+            Some(seat) => format!("player {}", seat + 1),
+            None => id.simple().to_string()[..6].to_owned(),
+        }
+    }
+
+    fn slot_name(&self, slot: Slot) -> String {
+        if slot.player == self.id() {
+            format!("your card {}", slot.index + 1)
+        } else {
+            format!("{}'s card {}", self.name(slot.player), slot.index + 1)
+        }
+    }
+
+    fn describe(&self, id: Uuid, decision: Decision) -> String {
+        let who = self.name(id);
+        match decision {
+            Decision::Discard => format!("{who} discarded"),
+            Decision::Replace { own } => format!("{who} replaced card {}", own + 1),
+            Decision::LookAtOwn { own } => format!("{who} looked at card {}", own + 1),
+            Decision::LookAtOther { other } => {
+                format!("{who} looked at {}", self.slot_name(other))
+            }
+            Decision::BlindSwap { own, other } => {
+                format!(
+                    "{who} swapped card {} with {}",
+                    own + 1,
+                    self.slot_name(other)
+                )
+            }
+            Decision::LookAndSwap { own, other } => {
+                let other = self.slot_name(other);
+                format!("{who} looked at card {} and {other}", own + 1)
+            }
+        }
+    }
+
+    /// The whole screen.
+    fn draw(&self) -> String {
+        let mut lines = Vec::new();
+
+        let view = self.state.view();
+        let round = self
+            .state
+            .round()
+            .map_or(String::new(), |round| format!("round {}", round + 1));
+        let discard = view.top_discard.map_or("-".to_owned(), card_cell);
+        lines.push(format!(
+            "{}  {round}  deck {}  discard {discard}",
+            style("CAMBIO").bold(),
+            view.deck_len,
+        ));
+        lines.push(String::new());
+
+        for hand in &view.hands {
+            let marker = if Some(hand.id) == self.state.turn() {
+                ">"
+            } else {
+                " "
+            };
+            let cards = hand
+                .cards
+                .iter()
+                .map(|card| card.map_or("[?? ]".to_owned(), card_cell))
+                .collect::<Vec<_>>()
+                .join(" ");
+            lines.push(format!("{marker} {:<9} {cards}", self.name(hand.id)));
+        }
+        if let Some(hand) = view.own_hand() {
+            let numbers = (1..=hand.cards.len())
+                .map(|n| format!("  {n}  "))
+                .collect::<String>();
+            lines.push(format!("{:11} {}", "", style(numbers).dim()));
+        }
+        lines.push(String::new());
+
+        let left = self
+            .state
+            .time_left(Instant::now())
+            .map(|left| left.as_secs());
+        let countdown = left.map_or(String::new(), |left| format!("  ({left}s)"));
+        let (ask, keys) = self.prompt_text();
+        lines.push(format!(
+            "{}{}",
+            style(ask).bold(),
+            style(countdown).yellow()
+        ));
+        lines.push(format!("{}  [q] quit", keys));
+        lines.push(String::new());
+
+        lines.extend(self.log.iter().map(|line| style(line).dim().to_string()));
+        lines.push(String::new());
+
+        lines.join("\n")
+    }
+
+    /// What the player is being asked, and the keys they can answer with.
+    fn prompt_text(&self) -> (String, String) {
+        match self.prompt {
+            Prompt::Wait => ("waiting...".to_owned(), String::new()),
+            Prompt::Lobby { players } => (
+                format!("in the lobby with {players} player(s)"),
+                "[s] start (host only)".to_owned(),
+            ),
+            Prompt::Decide { card } => {
+                let valid = valid_set(card, self.state.rules());
+                let keys = DECISION_KEYS
+                    .iter()
+                    .filter(|(_, kind, _)| valid.contains(kind))
+                    .map(|(key, _, name)| format!("[{key}] {name}"))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                (
+                    format!("you drew {}, what now?", label(card)),
+                    format!("{keys}  [c] call Cambio"),
+                )
+            }
+            Prompt::Target {
+                kind, own, player, ..
+            } => {
+                let ask = if needs_own(kind) && own.is_none() {
+                    format!("pick one of your cards (1-{})", self.own_len())
+                } else if player.is_none() {
+                    format!("pick a player (1-{})", self.opponents().count())
+                } else {
+                    let len = self
+                        .opponents()
+                        .find(|hand| Some(hand.id) == player)
+                        .map_or(0, |hand| hand.cards.len());
+                    format!("pick one of their cards (1-{len})")
+                };
+                (ask, "[esc] back".to_owned())
+            }
+            Prompt::Swap => (
+                "swap the cards you looked at?".to_owned(),
+                "[y] yes  [n] no".to_owned(),
+            ),
+            Prompt::Snap => (
+                "snap a card matching the discard pile!".to_owned(),
+                format!("[1-{}] snap your card", self.own_len()),
+            ),
+            Prompt::NewRound => (
+                "play another round?".to_owned(),
+                "[y] yes  [n] end the match".to_owned(),
+            ),
+        }
+    }
+}
+
+fn needs_own(kind: DecisionKind) -> bool {
+    matches!(
+        kind,
+        DecisionKind::Replace
+            | DecisionKind::LookAtOwn
+            | DecisionKind::BlindSwap
+            | DecisionKind::LookAndSwap
+    )
+}
+
+/// A card written short, like `10♥` or `Q♠`.
+fn label(card: Card) -> String {
+    let Card::Normal { suit, face } = card else {
+        return "Jk".to_owned();
+    };
+    let face = match face {
+        Face::King => "K",
+        Face::Queen => "Q",
+        Face::Jack => "J",
+        Face::Ten => "10",
+        Face::Nine => "9",
+        Face::Eight => "8",
+        Face::Seven => "7",
+        Face::Six => "6",
+        Face::Five => "5",
+        Face::Four => "4",
+        Face::Three => "3",
+        Face::Two => "2",
+        Face::Ace => "A",
+    };
+    let suit = match suit {
+        Suit::Hearts => '♥',
+        Suit::Diamonds => '♦',
+        Suit::Clubs => '♣',
+        Suit::Spades => '♠',
+    };
+    format!("{face}{suit}")
+}
+
+/// A card as it sits on the table, red suits in red.
+fn card_cell(card: Card) -> String {
+    let text = format!("[{:<3}]", label(card));
+    match card {
+        Card::Normal {
+            suit: Suit::Hearts | Suit::Diamonds,
+            ..
+        } => style(text).red().to_string(),
+        _ => text,
+    }
+}
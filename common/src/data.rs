@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{Card, Deck, STARTING_DECK_LEN};
+use crate::{rules::RuleSet, Card, STARTING_DECK_LEN};
 
+/// Who has joined the table.
+///
+/// Their cards are held by the engine, in `game::Game`.
 #[derive(Serialize, Deserialize)]
 pub struct GameData {
     players: Vec<PlayerData>,
@@ -58,7 +61,7 @@ impl Default for GameData {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayerData {
     id: uuid::Uuid,
-    pub(crate) cards: Vec<Card>,
+    cards: Vec<Card>,
 }
 
 impl PartialEq for PlayerData {
@@ -81,17 +84,13 @@ impl PlayerData {
         self.id
     }
 
-    pub fn score(&self) -> i32 {
-        self.cards.iter().map(|c| c.game_value() as i32).sum()
+    pub fn score(&self, rules: &RuleSet) -> i32 {
+        self.cards.iter().map(|c| c.game_value(rules) as i32).sum()
     }
 
     pub fn cards(&self) -> &[Card] {
         &self.cards
     }
-
-    pub fn cards_mut(&mut self) -> &mut [Card] {
-        &mut self.cards
-    }
 }
 
 impl Default for PlayerData {
@@ -100,7 +99,54 @@ impl Default for PlayerData {
     }
 }
 
-pub fn take_starting_cards(deck: &mut Deck, data: &mut GameData, player: usize) {
-    let cards_from_deck = deck.0.drain(..STARTING_DECK_LEN);
-    data.players[player].cards.extend(cards_from_deck);
+/// What one player can see of the table.
+///
+/// Cards are only in the view if the player has legitimately seen them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerView {
+    /// The player looking at the table.
+    pub id: uuid::Uuid,
+    /// Everyone's hand, in seat order.
+    pub hands: Vec<HandView>,
+    pub top_discard: Option<Card>,
+    /// Number of cards left to draw.
+    pub deck_len: usize,
+}
+
+impl PlayerView {
+    /// The hand of the player looking at the table.
+    pub fn own_hand(&self) -> Option<&HandView> {
+        self.hands.iter().find(|hand| hand.id == self.id)
+    }
+}
+
+/// Someone's hand, as seen by the player looking at it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandView {
+    pub id: uuid::Uuid,
+    /// The cards in hand order, `None` if the viewer hasn't seen it.
+    pub cards: Vec<Option<Card>>,
+}
+
+/// Everything a player needs to pick a game back up after rejoining.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resync {
+    pub view: PlayerView,
+    pub round: Option<usize>,
+    /// Whose turn it is, if a turn is being played.
+    pub turn: Option<uuid::Uuid>,
+}
+
+/// A player's place in a match played over several rounds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub id: uuid::Uuid,
+    /// Score from the last round played.
+    pub round: i32,
+    /// Score over every round played.
+    pub total: i32,
+    /// Number of rounds won.
+    pub wins: usize,
+    /// Knocked out of the match for reaching the points limit.
+    pub eliminated: bool,
 }
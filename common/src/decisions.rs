@@ -1,17 +1,66 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::{Card, Face};
+use crate::{rules::RuleSet, Card};
 
-#[repr(u8)]
+/// A slot in a player's hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slot {
+    /// The player who holds the card.
+    pub player: Uuid,
+    /// Index of the card in their hand.
+    pub index: usize,
+}
+
+/// What the player does with the card they have drawn.
+///
+/// Own slots are indices into the player's own hand,
+/// whereas [`Slot`]s point into someone elses.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Decision {
+    /// Place the card into the discard pile.
+    Discard,
+    /// Replace the card in hand with one in the deck, discarding it.
+    Replace { own: usize },
+    /// Discard the card and look at one of your own cards.
+    LookAtOwn { own: usize },
+    /// Discard the card and look at any card other than your own.
+    LookAtOther { other: Slot },
+    /// Choose a card of your own and someone elses to swap, without looking.
+    BlindSwap { own: usize, other: Slot },
+    /// Look at one of your own cards, and one of someone elses, then choose whether to swap them.
+    LookAndSwap { own: usize, other: Slot },
+}
+
+impl Decision {
+    /// The kind of decision, without any of its targets.
+    pub const fn kind(&self) -> DecisionKind {
+        match self {
+            Decision::Discard => DecisionKind::Discard,
+            Decision::Replace { .. } => DecisionKind::Replace,
+            Decision::LookAtOwn { .. } => DecisionKind::LookAtOwn,
+            Decision::LookAtOther { .. } => DecisionKind::LookAtOther,
+            Decision::BlindSwap { .. } => DecisionKind::BlindSwap,
+            Decision::LookAndSwap { .. } => DecisionKind::LookAndSwap,
+        }
+    }
+
+    /// Checks that this decision is part of a valid set.
+    pub fn is_valid(&self, valid_decisions: DecisionSet) -> bool {
+        valid_decisions.contains(&self.kind())
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecisionKind {
     /// Place the card into the discard pile.
     Discard,
     /// Replace the card in hand with one in the deck, discarding it.
     Replace,
     /// Discard the card and look at one of your own cards.
     LookAtOwn,
-    /// Discard the card and any card other than your own.
+    /// Discard the card and look at any card other than your own.
     LookAtOther,
     /// Choose a card of your own and someone elses to swap, without looking.
     BlindSwap,
@@ -19,22 +68,17 @@ pub enum Decision {
     LookAndSwap,
 }
 
-impl Decision {
-    /// All of the decisions from [`Decision`].
-    pub const ALL: [Decision; 6] = [
-        Decision::Discard,
-        Decision::Replace,
-        Decision::LookAtOwn,
-        Decision::LookAtOther,
-        Decision::BlindSwap,
-        Decision::LookAndSwap,
+impl DecisionKind {
+    /// All of the decisions from [`DecisionKind`].
+    pub const ALL: [DecisionKind; 6] = [
+        DecisionKind::Discard,
+        DecisionKind::Replace,
+        DecisionKind::LookAtOwn,
+        DecisionKind::LookAtOther,
+        DecisionKind::BlindSwap,
+        DecisionKind::LookAndSwap,
     ];
 
-    /// Checks that this decision is part of a valid set.
-    pub fn is_valid(&self, valid_decisions: DecisionSet) -> bool {
-        valid_decisions.contains(self)
-    }
-
     const fn discriminant(&self) -> u8 {
         // SAFETY: Because `Self` is marked `repr(u8)`, its layout is a `repr(C)` `union`
         // between `repr(C)` structs, each of which has the `u8` discriminant as its first
@@ -47,18 +91,17 @@ impl Decision {
 pub struct DecisionSet(u64);
 
 /// Returns the set of valid decisions given the card.
-pub const fn valid_set(card: Card) -> DecisionSet {
+pub fn valid_set(card: Card, rules: &RuleSet) -> DecisionSet {
     // All cards can Discard and Replace.
-    const BASE: DecisionSet = DecisionSet::from_array([Decision::Discard, Decision::Replace]);
+    const BASE: DecisionSet =
+        DecisionSet::from_array([DecisionKind::Discard, DecisionKind::Replace]);
 
     match card {
-        Card::Normal { face, .. } => match face {
-            Face::King => BASE.and(Decision::LookAndSwap),
-            Face::Jack | Face::Queen => BASE.and(Decision::BlindSwap),
-            Face::Nine | Face::Ten => BASE.and(Decision::LookAtOther),
-            Face::Seven | Face::Eight => BASE.and(Decision::LookAtOwn),
-            _ => BASE,
-        },
+        Card::Normal { face, .. } => rules
+            .powers
+            .iter()
+            .filter(|(power_face, _)| *power_face == face)
+            .fold(BASE, |set, &(_, decision)| set.and(decision)),
         Card::Joker => BASE,
     }
 }
@@ -70,15 +113,18 @@ impl DecisionSet {
         Self::EMPTY
     }
 
-    pub const fn only(decision: Decision) -> Self {
+    pub const fn only(decision: DecisionKind) -> Self {
         DecisionSet(Self::to_bit(decision))
     }
 
-    pub fn into_vec(self) -> Vec<Decision> {
-        Decision::ALL.into_iter().filter(|decision| self.contains(decision)).collect()
+    pub fn into_vec(self) -> Vec<DecisionKind> {
+        DecisionKind::ALL
+            .into_iter()
+            .filter(|decision| self.contains(decision))
+            .collect()
     }
 
-    pub const fn from_array<const N: usize>(decisions: [Decision; N]) -> Self {
+    pub const fn from_array<const N: usize>(decisions: [DecisionKind; N]) -> Self {
         let mut this = Self::new();
         let mut i = 0;
         while i < N {
@@ -88,17 +134,17 @@ impl DecisionSet {
         this
     }
 
-    pub fn contains(&self, decision: &Decision) -> bool {
+    pub fn contains(&self, decision: &DecisionKind) -> bool {
         let bit = Self::to_bit(*decision);
         bit == self.0 & bit
     }
-    pub const fn and(mut self, decision: Decision) -> Self {
+    pub const fn and(mut self, decision: DecisionKind) -> Self {
         self.0 |= Self::to_bit(decision);
         self
     }
 
     #[inline(always)]
-    const fn to_bit(decision: Decision) -> u64 {
+    const fn to_bit(decision: DecisionKind) -> u64 {
         1_u64 << decision.discriminant()
     }
 }
@@ -111,12 +157,31 @@ impl Default for DecisionSet {
 
 #[test]
 fn set_impl() {
-    let set = const { DecisionSet::from_array([Decision::Discard, Decision::Replace]) };
-    assert!(set.contains(&Decision::Discard));
-    assert!(set.contains(&Decision::Replace));
+    let set = const { DecisionSet::from_array([DecisionKind::Discard, DecisionKind::Replace]) };
+    assert!(set.contains(&DecisionKind::Discard));
+    assert!(set.contains(&DecisionKind::Replace));
 
-    let set = const { DecisionSet::only(Decision::Discard) };
+    let set = const { DecisionSet::only(DecisionKind::Discard) };
 
-    assert!(set.contains(&Decision::Discard));
-    assert!(!set.contains(&Decision::Replace));
+    assert!(set.contains(&DecisionKind::Discard));
+    assert!(!set.contains(&DecisionKind::Replace));
+}
+
+#[test]
+fn powers_from_rules() {
+    use crate::{rules::Preset, Face, Suit};
+
+    let king = Card::Normal {
+        suit: Suit::Spades,
+        face: Face::King,
+    };
+    let mut rules = Preset::Standard.rules();
+    assert!(valid_set(king, &rules).contains(&DecisionKind::LookAndSwap));
+    assert!(!valid_set(Card::Joker, &rules).contains(&DecisionKind::LookAndSwap));
+
+    rules.powers.clear();
+    assert_eq!(
+        valid_set(king, &rules).into_vec(),
+        [DecisionKind::Discard, DecisionKind::Replace]
+    );
 }
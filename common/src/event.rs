@@ -1,2 +1,60 @@
 pub mod client;
 pub mod server;
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the events sent between client and server.
+///
+/// Bumped whenever an event changes in a way the other side can't read.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// The oldest version the server will still talk to.
+///
+/// Version 1 clients don't offer any formats, so they're spoken to in JSON.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features, exchanged in the `Hello`s so each side knows what the other can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Capability {
+    /// Seats are kept for players who drop out, so they can rejoin.
+    Rejoin,
+    /// Decision and snap timers are announced, to count down with.
+    Timers,
+    /// The server pings, and the client answers with a pong.
+    Heartbeat,
+}
+
+impl Capability {
+    pub const ALL: &'static [Self] = &[Self::Rejoin, Self::Timers, Self::Heartbeat];
+}
+
+/// How the server checks a connection is still alive, and the client checks the server is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    /// Time between pings.
+    pub interval: Duration,
+    /// Pings that can go by without hearing anything, before the connection counts as dead.
+    pub misses: u32,
+}
+
+impl Heartbeat {
+    /// How long a connection can go quiet for.
+    pub fn limit(&self) -> Duration {
+        self.interval * self.misses
+    }
+}
+
+/// Whether a peer speaking `version` can talk to us.
+pub fn is_compatible(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+#[test]
+fn only_supported_versions_are_compatible() {
+    assert!(is_compatible(PROTOCOL_VERSION));
+    assert!(is_compatible(MIN_PROTOCOL_VERSION));
+    assert!(!is_compatible(MIN_PROTOCOL_VERSION - 1));
+    assert!(!is_compatible(PROTOCOL_VERSION + 1));
+}
@@ -1,21 +1,52 @@
 use serde::{Deserialize, Serialize};
 
-use crate::decisions::Decision;
+use crate::decisions::{Decision, Slot};
+use crate::rules::Preset;
+use crate::stream::Format;
+
+use super::Capability;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
+    /// The first thing sent, before joining.
+    Hello {
+        protocol_version: u32,
+        client_name: String,
+        capabilities: Vec<Capability>,
+        /// Wire formats the client can speak, best first.
+        #[serde(default)]
+        formats: Vec<Format>,
+    },
     Join(Join),
     GetLobbyInfo,
     Start,
-    Snap,
+    /// Host picks the house rules whilst in the lobby.
+    ChooseRules(Preset),
+    /// Snap a card matching the top of the discard pile.
+    ///
+    /// When snapping someone elses card, `give` is the slot of
+    /// one of your own cards to hand over in its place.
+    Snap {
+        slot: Slot,
+        give: Option<usize>,
+    },
     Decision(Decision),
+    ChooseSwap(bool),
+    CallCambio,
     ConfirmNewRound,
     SkipNewRound,
+    /// Answer to a server `Ping`.
+    Pong,
     Leave,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Join {
     New,
-    Existing(uuid::Uuid),
+    /// Take back a seat after dropping out,
+    /// `token` is the one given with the id to prove it's theirs.
+    Existing {
+        id: uuid::Uuid,
+        token: uuid::Uuid,
+    },
 }
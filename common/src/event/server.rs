@@ -1,36 +1,68 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::decisions::{Decision, Slot};
+use crate::rules::RuleSet;
+use crate::stream::Format;
 use crate::Card;
 
-use crate::data::PlayerData;
+use crate::data::{PlayerView, Resync, Standing};
+
+use super::{Capability, Heartbeat};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
+    /// Response to client `Hello`, the client can go on to join.
+    ///
+    /// Everything after is sent in `format`, by both sides.
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<Capability>,
+        #[serde(default)]
+        format: Format,
+        /// How often the server pings, if the client can answer.
+        #[serde(default)]
+        heartbeat: Option<Heartbeat>,
+    },
+    /// Response to client `Hello` when the server can't talk to it.
+    ///
+    /// The connection is closed after.
+    IncompatibleVersion {
+        client: u32,
+        min_supported: u32,
+        max_supported: u32,
+    },
     /// Ready to start serving event loop for client.
     Enter,
     /// The game has restarted
     Restart,
     /// Information about the lobby.
-    /// 
+    ///
     /// Can be requested any time.
-    LobbyInfo {
-        player_count: usize,
-    },
+    LobbyInfo { player_count: usize },
+    /// The house rules for the game.
+    ///
+    /// Sent when the host picks them, and when the game starts.
+    Rules(RuleSet),
+    /// How long players have to make a decision, and to snap.
+    ///
+    /// Sent when the game starts, so clients can count down.
+    Timers { decision: Duration, snap: Duration },
     /// Response to client `Join` request.
-    /// 
+    ///
+    /// The `token` lets the player rejoin as `id` if they drop out.
     /// Must not be broadcasted.
-    AssignId {
-        id: Uuid,
-    },
+    AssignId { id: Uuid, token: Uuid },
     /// A player joined
-    Joined {
-        id: Uuid,
-    },
+    Joined { id: Uuid },
     /// A player left
-    Left {
-        id: Uuid,
-    },
+    Left { id: Uuid },
+    /// The table as it is now, for a player who has just rejoined.
+    ///
+    /// Followed by whatever the table is waiting on.
+    Resync(Resync),
     /// Start of a round
     RoundStart(usize),
     /// Reset cards and shuffle
@@ -39,8 +71,8 @@ pub enum Event {
     ///
     /// Clients do not need to know about their cards.
     FirstDraw,
-    /// Players view their front 2 cards
-    FirstPeek(Card, Card),
+    /// Players view their front cards, as many as the rules allow
+    FirstPeek(PlayerView),
     /// Turn of player has started
     TurnStart { id: Uuid },
     /// Card is drawn from deck
@@ -48,17 +80,50 @@ pub enum Event {
     /// Waiting for the player to make a decision
     WaitingForDecision,
     /// Play the action of the card
-    PlayAction,
+    PlayAction { id: Uuid, decision: Decision },
+    /// Card placed face up on the discard pile
+    Discard(Card),
+    /// The deck ran out, the discard pile bar its top card was shuffled into it
+    Reshuffle,
+    /// A card the player has been allowed to look at
+    ///
+    /// Must not be broadcasted.
+    Peek { slot: Slot, card: Card },
+    /// Waiting for the player to choose whether to swap the cards they looked at
+    WaitingForSwapChoice,
+    /// Two cards have swapped places
+    Swapped(Slot, Slot),
     /// Wait for a potential snap
     WaitingForSnap,
+    /// A player snapped a matching card onto the discard pile
+    Snapped { id: Uuid, slot: Slot, card: Card },
+    /// A player snapped a card that didn't match, it stays where it was
+    WrongSnap { id: Uuid, slot: Slot, card: Card },
+    /// A player draws a card as a penalty, without looking at it
+    Penalty { id: Uuid },
+    /// A player gave one of their cards to fill the slot they snapped
+    GaveCard { from: Slot, to: Slot },
+    /// The last input sent was turned down
+    Rejected { reason: String },
     /// Turn has ended
     EndTurn,
-    /// Cambio has been called
-    CambioCall,
+    /// Cambio has been called, everyone else gets one last turn
+    CambioCall { id: Uuid },
     /// Show all cards
-    ShowAll(Vec<PlayerData>),
-    /// Announce winner
-    Winner(Winner),
+    ShowAll(PlayerView),
+    /// Announce the winner of the round
+    Winner { id: Uuid },
+    /// Everyone's scores after the round, best first
+    RoundScores(Vec<Standing>),
+    /// The match is over, the standings are best first
+    ///
+    /// `seed` reproduces every deal of the match, for bug reports,
+    /// it's only safe to tell now there are no cards left to play.
+    MatchResult {
+        winner: Uuid,
+        standings: Vec<Standing>,
+        seed: u64,
+    },
     /// End of round
     RoundEnd,
     /// Ask all clients to config if they wish to play again.
@@ -67,10 +132,6 @@ pub enum Event {
     GameEnd,
     /// Server Closing
     ServerClosing,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Winner {
-    Player { uuid: Uuid },
-    Tied,
+    /// Check the client is still there, it should answer with a `Pong`.
+    Ping,
 }
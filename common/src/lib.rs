@@ -2,8 +2,10 @@ pub mod data;
 pub mod event;
 pub mod stream;
 pub mod decisions;
+pub mod rules;
 
 use rand::seq::SliceRandom;
+use rules::RuleSet;
 use serde::{Deserialize, Serialize};
 
 macro_rules! SuitSet {
@@ -46,13 +48,13 @@ SuitSet!(Diamonds);
 SuitSet!(Clubs);
 SuitSet!(Spades);
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Card {
     Normal { suit: Suit, face: Face },
     Joker,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Suit {
     Hearts,
     Diamonds,
@@ -72,12 +74,21 @@ impl Suit {
 }
 
 impl Card {
-    pub const fn game_value(&self) -> i8 {
+    /// Cards of the same rank can be snapped onto each other, whatever their suit.
+    pub fn same_rank(&self, other: &Card) -> bool {
+        match (self, other) {
+            (Card::Normal { face: a, .. }, Card::Normal { face: b, .. }) => a == b,
+            (Card::Joker, Card::Joker) => true,
+            _ => false,
+        }
+    }
+
+    pub fn game_value(&self, rules: &RuleSet) -> i8 {
         match self {
             Card::Normal { suit, face } => {
                 let color = suit.color();
                 match (color, face) {
-                    (Color::Red, Face::King) => -2,
+                    (Color::Red, Face::King) => rules.red_king_value,
                     (Color::Black, Face::King) => 13,
                     (_, Face::Queen) => 12,
                     (_, Face::Jack) => 11,
@@ -93,7 +104,7 @@ impl Card {
                     (_, Face::Ace) => 1,
                 }
             }
-            Card::Joker => 0,
+            Card::Joker => rules.joker_value,
         }
     }
 }
@@ -198,7 +209,7 @@ pub const FULL_DECK: [Card; 54] = [
 
 pub const STARTING_DECK_LEN: usize = 4;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Deck(Vec<Card>);
 
 impl Deck {
@@ -210,10 +221,19 @@ impl Deck {
         self.0.pop()
     }
 
+    /// Put `cards` back into the deck, on the bottom.
+    pub fn refill(&mut self, cards: impl IntoIterator<Item = Card>) {
+        self.0.splice(0..0, cards);
+    }
+
     pub fn shuffle<R: rand::Rng + ?Sized>(&mut self, rng: &mut R) {
         self.0.shuffle(rng);
     }
 
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
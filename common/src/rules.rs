@@ -0,0 +1,127 @@
+use std::str::FromStr;
+
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+
+use crate::{decisions::DecisionKind, Face, STARTING_DECK_LEN};
+
+/// House rules for a game of Cambio.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSet {
+    /// Number of cards dealt to each player.
+    pub starting_cards: usize,
+    /// Number of their own cards each player looks at after the deal.
+    pub first_peek: usize,
+    /// Extra decisions a card grants when drawn, by face.
+    pub powers: Vec<(Face, DecisionKind)>,
+    /// Value of a red king, black kings are always worth 13.
+    pub red_king_value: i8,
+    /// Value of a joker.
+    pub joker_value: i8,
+    /// Whether players can snap cards out of someone elses hand.
+    pub snap_opponents: bool,
+    /// Points added to a Cambio caller who doesn't finish with the lowest score.
+    pub cambio_penalty: i32,
+    /// When the match is over.
+    pub match_end: MatchEnd,
+    /// End the round as soon as the deck runs out,
+    /// instead of shuffling the discard pile back into it.
+    pub end_on_empty_deck: bool,
+}
+
+/// How a match of several rounds comes to an end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MatchEnd {
+    /// Play a fixed number of rounds.
+    Rounds(usize),
+    /// Players whose total reaches the limit are knocked out,
+    /// until there is only one left.
+    PointsLimit(i32),
+}
+
+/// Named [`RuleSet`]s that can be picked in the lobby.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// The rules from the official website.
+    #[default]
+    Standard,
+    /// Look at more of your hand, and nobody can snap your cards.
+    Friendly,
+    /// Six cards each, so rounds last longer.
+    SixCards,
+    /// Red kings aren't as good, and failing a Cambio call hurts more.
+    Cutthroat,
+}
+
+const STANDARD_POWERS: [(Face, DecisionKind); 7] = [
+    (Face::King, DecisionKind::LookAndSwap),
+    (Face::Queen, DecisionKind::BlindSwap),
+    (Face::Jack, DecisionKind::BlindSwap),
+    (Face::Ten, DecisionKind::LookAtOther),
+    (Face::Nine, DecisionKind::LookAtOther),
+    (Face::Eight, DecisionKind::LookAtOwn),
+    (Face::Seven, DecisionKind::LookAtOwn),
+];
+
+impl FromStr for Preset {
+    type Err = serde::de::value::Error;
+
+    /// Parse the same names used in config files, e.g. `six-cards`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
+impl Preset {
+    /// All of the presets from [`Preset`].
+    pub const ALL: [Preset; 4] = [
+        Preset::Standard,
+        Preset::Friendly,
+        Preset::SixCards,
+        Preset::Cutthroat,
+    ];
+
+    pub fn rules(self) -> RuleSet {
+        let standard = RuleSet {
+            starting_cards: STARTING_DECK_LEN,
+            first_peek: 2,
+            powers: STANDARD_POWERS.to_vec(),
+            red_king_value: -2,
+            joker_value: 0,
+            snap_opponents: true,
+            cambio_penalty: 10,
+            match_end: MatchEnd::PointsLimit(100),
+            end_on_empty_deck: false,
+        };
+
+        match self {
+            Preset::Standard => standard,
+            Preset::Friendly => RuleSet {
+                first_peek: 3,
+                snap_opponents: false,
+                cambio_penalty: 0,
+                match_end: MatchEnd::Rounds(5),
+                ..standard
+            },
+            Preset::SixCards => RuleSet {
+                starting_cards: 6,
+                first_peek: 3,
+                ..standard
+            },
+            Preset::Cutthroat => RuleSet {
+                first_peek: 1,
+                red_king_value: 0,
+                joker_value: -1,
+                cambio_penalty: 20,
+                match_end: MatchEnd::PointsLimit(50),
+                ..standard
+            },
+        }
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Preset::Standard.rules()
+    }
+}
@@ -1,23 +1,332 @@
-use tokio::net::{
-    tcp::{OwnedReadHalf, OwnedWriteHalf},
-    TcpStream,
+use std::{
+    io,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
 };
-use tokio_serde::{formats::SymmetricalJson, Framed};
-use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 
-pub type Read<Value> =
-    Framed<FramedRead<OwnedReadHalf, LengthDelimitedCodec>, Value, Value, SymmetricalJson<Value>>;
-pub type Write<Value> =
-    Framed<FramedWrite<OwnedWriteHalf, LengthDelimitedCodec>, Value, Value, SymmetricalJson<Value>>;
+use futures::{Sink, Stream};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::{
+    bytes::{Bytes, BytesMut},
+    codec::{FramedRead, FramedWrite, LengthDelimitedCodec},
+};
+
+/// How events are encoded on the wire.
+///
+/// Every connection starts out in JSON, the client and server pick another in their `Hello`s.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// Easy to read, for debugging.
+    #[default]
+    Json,
+    /// Compact binary.
+    MessagePack,
+}
+
+impl Format {
+    /// Best first.
+    pub const ALL: &'static [Self] = &[Self::MessagePack, Self::Json];
+
+    pub fn encode<V: Serialize>(self, value: &V) -> io::Result<Bytes> {
+        let bytes = match self {
+            Format::Json => serde_json::to_vec(value).map_err(io::Error::other)?,
+            Format::MessagePack => rmp_serde::to_vec(value).map_err(io::Error::other)?,
+        };
+        Ok(bytes.into())
+    }
+
+    pub fn decode<V: DeserializeOwned>(self, bytes: &[u8]) -> io::Result<V> {
+        let invalid = io::ErrorKind::InvalidData;
+        match self {
+            Format::Json => serde_json::from_slice(bytes).map_err(|e| io::Error::new(invalid, e)),
+            Format::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|e| io::Error::new(invalid, e))
+            }
+        }
+    }
+}
+
+type Frames = Pin<Box<dyn Stream<Item = io::Result<BytesMut>> + Send>>;
+type FrameSink = Pin<Box<dyn Sink<Bytes, Error = io::Error> + Send>>;
+
+/// Events coming in, one per frame.
+pub struct Read<Value> {
+    frames: Frames,
+    format: Format,
+    value: PhantomData<fn() -> Value>,
+}
+
+/// Events going out, one per frame.
+pub struct Write<Value> {
+    frames: FrameSink,
+    format: Format,
+    value: PhantomData<fn(Value)>,
+}
 
-pub fn split<R, W>(stream: TcpStream) -> (Read<R>, Write<W>) {
-    let (r, w) = stream.into_split();
+impl<Value> Read<Value> {
+    /// Read from any transport that splits what it receives into `frames`, starting in JSON.
+    pub fn new(frames: impl Stream<Item = io::Result<BytesMut>> + Send + 'static) -> Self {
+        Self {
+            frames: Box::pin(frames),
+            format: Format::default(),
+            value: PhantomData,
+        }
+    }
+
+    /// Decode the frames that come after this in `format`.
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+}
 
-    let read = FramedRead::new(r, LengthDelimitedCodec::new());
-    let write = FramedWrite::new(w, LengthDelimitedCodec::new());
+impl<Value> Write<Value> {
+    /// Write to any transport that sends whole `frames`, starting in JSON.
+    pub fn new(frames: impl Sink<Bytes, Error = io::Error> + Send + 'static) -> Self {
+        Self {
+            frames: Box::pin(frames),
+            format: Format::default(),
+            value: PhantomData,
+        }
+    }
 
-    let read = tokio_serde::SymmetricallyFramed::new(read, SymmetricalJson::<R>::default());
-    let write = tokio_serde::SymmetricallyFramed::new(write, SymmetricalJson::<W>::default());
+    /// Encode the events sent after this in `format`.
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+}
+
+impl<Value: DeserializeOwned> Stream for Read<Value> {
+    type Item = io::Result<Value>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let format = self.format;
+        self.frames
+            .as_mut()
+            .poll_next(cx)
+            .map(|frame| frame.map(|frame| format.decode(&frame?)))
+    }
+}
+
+impl<Value: Serialize> Sink<Value> for Write<Value> {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.frames.as_mut().poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, value: Value) -> io::Result<()> {
+        let frame = self.format.encode(&value)?;
+        self.frames.as_mut().start_send(frame)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.frames.as_mut().poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.frames.as_mut().poll_close(cx)
+    }
+}
+
+/// Split a byte stream, like TCP or TLS, into events, each frame prefixed by its length.
+pub fn split<R, W, S>(stream: S) -> (Read<R>, Write<W>)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (r, w) = tokio::io::split(stream);
+
+    let read = Read::new(FramedRead::new(r, LengthDelimitedCodec::new()));
+    let write = Write::new(FramedWrite::new(w, LengthDelimitedCodec::new()));
 
     (read, write)
 }
+
+#[test]
+fn every_event_round_trips_in_every_format() {
+    use std::{fmt::Debug, time::Duration};
+
+    use crate::{
+        data::{HandView, PlayerView, Resync, Standing},
+        decisions::{Decision, Slot},
+        event::{client, server, Capability, Heartbeat},
+        rules::Preset,
+        Card, Face, Suit,
+    };
+
+    fn round_trip<V: Serialize + DeserializeOwned + Debug>(format: Format, value: V) {
+        let bytes = format.encode(&value).unwrap();
+        let decoded = format.decode::<V>(&bytes).unwrap();
+        assert_eq!(
+            format!("{decoded:?}"),
+            format!("{value:?}"),
+            "in {format:?}"
+        );
+    }
+
+    let (id, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    let slot = Slot {
+        player: other,
+        index: 3,
+    };
+    let card = Card::Normal {
+        suit: Suit::Hearts,
+        face: Face::King,
+    };
+    let view = PlayerView {
+        id,
+        hands: vec![
+            HandView {
+                id,
+                cards: vec![Some(card), None, Some(Card::Joker), None],
+            },
+            HandView {
+                id: other,
+                cards: vec![None; 4],
+            },
+        ],
+        top_discard: Some(Card::Joker),
+        deck_len: 44,
+    };
+    let standings = vec![Standing {
+        id,
+        round: -1,
+        total: 12,
+        wins: 2,
+        eliminated: false,
+    }];
+
+    // one of each
+    let client_events = [
+        client::Event::Hello {
+            protocol_version: 3,
+            client_name: "test".into(),
+            capabilities: Capability::ALL.to_vec(),
+            formats: Format::ALL.to_vec(),
+        },
+        client::Event::Join(client::Join::New),
+        client::Event::Join(client::Join::Existing { id, token: other }),
+        client::Event::GetLobbyInfo,
+        client::Event::Start,
+        client::Event::ChooseRules(Preset::Cutthroat),
+        client::Event::Snap {
+            slot,
+            give: Some(1),
+        },
+        client::Event::Decision(Decision::LookAndSwap {
+            own: 0,
+            other: slot,
+        }),
+        client::Event::ChooseSwap(true),
+        client::Event::CallCambio,
+        client::Event::ConfirmNewRound,
+        client::Event::SkipNewRound,
+        client::Event::Pong,
+        client::Event::Leave,
+    ];
+
+    let server_events = [
+        server::Event::Hello {
+            protocol_version: 3,
+            capabilities: Capability::ALL.to_vec(),
+            format: Format::MessagePack,
+            heartbeat: Some(Heartbeat {
+                interval: Duration::from_secs(5),
+                misses: 3,
+            }),
+        },
+        server::Event::IncompatibleVersion {
+            client: 7,
+            min_supported: 1,
+            max_supported: 2,
+        },
+        server::Event::Enter,
+        server::Event::Restart,
+        server::Event::LobbyInfo { player_count: 3 },
+        server::Event::Rules(Preset::SixCards.rules()),
+        server::Event::Timers {
+            decision: Duration::from_secs(10),
+            snap: Duration::from_millis(2500),
+        },
+        server::Event::AssignId { id, token: other },
+        server::Event::Joined { id },
+        server::Event::Left { id },
+        server::Event::Resync(Resync {
+            view: view.clone(),
+            round: Some(2),
+            turn: None,
+        }),
+        server::Event::RoundStart(2),
+        server::Event::Setup,
+        server::Event::FirstDraw,
+        server::Event::FirstPeek(view.clone()),
+        server::Event::TurnStart { id },
+        server::Event::DrawCard(card),
+        server::Event::WaitingForDecision,
+        server::Event::PlayAction {
+            id,
+            decision: Decision::BlindSwap {
+                own: 2,
+                other: slot,
+            },
+        },
+        server::Event::Discard(card),
+        server::Event::Reshuffle,
+        server::Event::Peek { slot, card },
+        server::Event::WaitingForSwapChoice,
+        server::Event::Swapped(
+            slot,
+            Slot {
+                player: id,
+                index: 0,
+            },
+        ),
+        server::Event::WaitingForSnap,
+        server::Event::Snapped { id, slot, card },
+        server::Event::WrongSnap { id, slot, card },
+        server::Event::Penalty { id },
+        server::Event::GaveCard {
+            from: Slot {
+                player: id,
+                index: 1,
+            },
+            to: slot,
+        },
+        server::Event::Rejected {
+            reason: "not your turn".into(),
+        },
+        server::Event::EndTurn,
+        server::Event::CambioCall { id },
+        server::Event::ShowAll(view),
+        server::Event::Winner { id },
+        server::Event::RoundScores(standings.clone()),
+        server::Event::MatchResult {
+            winner: id,
+            standings,
+            seed: 42,
+        },
+        server::Event::RoundEnd,
+        server::Event::ConfirmNewRound,
+        server::Event::GameEnd,
+        server::Event::ServerClosing,
+        server::Event::Ping,
+    ];
+
+    for &format in Format::ALL {
+        for event in &client_events {
+            round_trip(format, event.clone());
+        }
+        for event in &server_events {
+            round_trip(format, event.clone());
+        }
+    }
+
+    // clients from before formats were negotiated still get to say hello
+    let hello = r#"{"Hello":{"protocol_version":1,"client_name":"old","capabilities":[]}}"#;
+    let hello = Format::Json
+        .decode::<client::Event>(hello.as_bytes())
+        .unwrap();
+    assert!(matches!(hello, client::Event::Hello { formats, .. } if formats.is_empty()));
+}
@@ -0,0 +1,105 @@
+//! Feed a game arbitrary client events from arbitrary players,
+//! the same way the server would, with the clock jumping around in between.
+//!
+//! Run with `cargo fuzz run client_events`.
+
+#![no_main]
+
+use std::time::{Duration, Instant};
+
+use arbitrary::{Result, Unstructured};
+use common::{
+    decisions::{Decision, Slot},
+    event::client::Event,
+    rules::Preset,
+};
+use game::{Game, State, Timings};
+use libfuzzer_sys::fuzz_target;
+use uuid::Uuid;
+
+fn slot(u: &mut Unstructured, ids: &[Uuid]) -> Result<Slot> {
+    Ok(Slot {
+        player: *u.choose(ids)?,
+        index: u.int_in_range(0..=8)?,
+    })
+}
+
+fn decision(u: &mut Unstructured, ids: &[Uuid]) -> Result<Decision> {
+    let own = u.int_in_range(0..=8)?;
+    Ok(match u.int_in_range(0..=5)? {
+        0 => Decision::Discard,
+        1 => Decision::Replace { own },
+        2 => Decision::LookAtOwn { own },
+        3 => Decision::LookAtOther {
+            other: slot(u, ids)?,
+        },
+        4 => Decision::BlindSwap {
+            own,
+            other: slot(u, ids)?,
+        },
+        _ => Decision::LookAndSwap {
+            own,
+            other: slot(u, ids)?,
+        },
+    })
+}
+
+/// The events a client can send once the game has started.
+fn event(u: &mut Unstructured, ids: &[Uuid]) -> Result<Event> {
+    Ok(match u.int_in_range(0..=5)? {
+        0 => Event::Snap {
+            slot: slot(u, ids)?,
+            give: u.arbitrary::<Option<u8>>()?.map(usize::from),
+        },
+        1 => Event::Decision(decision(u, ids)?),
+        2 => Event::ChooseSwap(u.arbitrary()?),
+        3 => Event::CallCambio,
+        4 => Event::ConfirmNewRound,
+        _ => Event::SkipNewRound,
+    })
+}
+
+fn play(u: &mut Unstructured) -> Result<()> {
+    let preset = *u.choose(&Preset::ALL)?;
+    let mut game = Game::new_with_seed(preset.rules(), Timings::default(), u.arbitrary()?);
+    let ids = (0..u.int_in_range(2..=8)?)
+        .map(|_| Uuid::new_v4())
+        .collect::<Vec<_>>();
+    for &id in &ids {
+        game.join(id);
+    }
+
+    let mut now = Instant::now();
+    let mut finished = false;
+    while !u.is_empty() {
+        let from = *u.choose(&ids)?;
+        match event(u, &ids)? {
+            Event::Snap { slot, give } => game.handle_snap(from, slot, give, now),
+            Event::Decision(decision) => {
+                let _ = game.handle_decision(from, decision, now);
+            }
+            Event::ChooseSwap(swap) => {
+                let _ = game.handle_swap_choice(from, swap, now);
+            }
+            Event::CallCambio => {
+                game.cambio_call(from, now);
+            }
+            Event::ConfirmNewRound => game.confirm_new_round(ids.len(), now),
+            _ => game.skip_new_round(),
+        }
+
+        now += Duration::from_millis(u.int_in_range(0..=20_000)?);
+        game.advance(now);
+        while game.poll_events().is_some() {}
+
+        // nothing brings a finished game back
+        assert!(!finished || matches!(game.current_state(), State::Finished));
+        finished = matches!(game.current_state(), State::Finished);
+    }
+
+    Ok(())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let _ = play(&mut Unstructured::new(data));
+});
@@ -3,10 +3,31 @@ use std::{
     time::{Duration, Instant},
 };
 
-use common::{decisions::Decision, Card, Deck};
+use common::{
+    data::{HandView, PlayerView, Standing},
+    decisions::{valid_set, Decision, Slot},
+    rules::{MatchEnd, RuleSet},
+    Card, Deck,
+};
+use rand::SeedableRng;
+use replay::Input;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub mod replay;
+mod snapshot;
+mod timings;
 
-#[derive(Debug, Clone, Copy)]
-pub enum State {
+pub use snapshot::Snapshot;
+pub use timings::Timings;
+
+/// Where the game is up to.
+///
+/// Waiting states hold a timer, which is the [`Instant`] they started waiting
+/// for a running game, and the time they have left in a [`Snapshot`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum State<T = Instant> {
     Pregame,
     StartRound {
         round: usize,
@@ -24,22 +45,33 @@ pub enum State {
     WaitingForDecision {
         round: usize,
         turn: usize,
-        started: Instant,
+        card: Card,
+        started: T,
     },
     PlayDecision {
         round: usize,
         turn: usize,
+        card: Card,
         decision: Decision,
     },
+    WaitingForSwap {
+        round: usize,
+        turn: usize,
+        own: usize,
+        other: Slot,
+        started: T,
+    },
     WaitingForSnaps {
         round: usize,
         turn: usize,
-        started: Instant,
+        started: T,
     },
     Snapped {
         round: usize,
         turn: usize,
-        card: Card,
+        snapper: Uuid,
+        slot: Slot,
+        give: Option<usize>,
     },
     EndTurn {
         round: usize,
@@ -48,60 +80,364 @@ pub enum State {
     EndRound {
         round: usize,
     },
+    ShowingAll {
+        round: usize,
+        started: T,
+    },
     FindWinner {
         round: usize,
     },
     WaitingForNewRound {
         round: usize,
         confirmations: usize,
-        started: Instant,
+        started: T,
     },
     CambioCall {
         round: usize,
+        turn: usize,
+        caller: Uuid,
     },
     Finished,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
-    Setup,
+    /// The seed the game was created with.
+    Setup(u64),
     FirstDraw,
     FirstPeek,
     StartRound(usize),
     StartTurn(usize),
     DrawCard(usize, Card),
     WaitForDecision,
+    PlayDecision(usize, Decision),
+    Discard(Card),
+    /// The deck ran out, the discard pile bar its top card was shuffled back in.
+    Reshuffle,
+    Peek(usize, Slot, Card),
+    WaitForSwap,
+    Swap(Slot, Slot),
     WaitForSnap,
+    Snapped(Uuid, Slot, Card),
+    WrongSnap(Uuid, Slot, Card),
+    Penalty(Uuid),
+    GiveCard(Slot, Slot),
     EndTurn(usize),
-    WaitForNewRound { confirmations: usize },
+    WaitForNewRound {
+        confirmations: usize,
+    },
     EndRound(usize),
-    Cambio,
-    FindWinner,
+    Cambio(Uuid),
+    ShowAll,
+    /// The player with the lowest score for the round.
+    FindWinner(Uuid),
+    /// Standings after the round has been scored.
+    RoundScores(Vec<Standing>),
+    /// The match is over, won by the player, with the standings in order.
+    MatchResult(Uuid, Vec<Standing>),
     Exit,
 }
 
+impl<T> State<T> {
+    /// The round being played, if there is one.
+    pub fn round(&self) -> Option<usize> {
+        match *self {
+            State::Pregame | State::Finished => None,
+            State::StartRound { round, .. }
+            | State::StartTurn { round, .. }
+            | State::DrawCard { round, .. }
+            | State::WaitingForDecision { round, .. }
+            | State::PlayDecision { round, .. }
+            | State::WaitingForSwap { round, .. }
+            | State::WaitingForSnaps { round, .. }
+            | State::Snapped { round, .. }
+            | State::EndTurn { round, .. }
+            | State::EndRound { round }
+            | State::ShowingAll { round, .. }
+            | State::FindWinner { round }
+            | State::WaitingForNewRound { round, .. }
+            | State::CambioCall { round, .. } => Some(round),
+        }
+    }
+
+    /// The turn being played, if there is one.
+    pub fn turn(&self) -> Option<usize> {
+        match *self {
+            State::StartTurn { turn, .. }
+            | State::DrawCard { turn, .. }
+            | State::WaitingForDecision { turn, .. }
+            | State::PlayDecision { turn, .. }
+            | State::WaitingForSwap { turn, .. }
+            | State::WaitingForSnaps { turn, .. }
+            | State::Snapped { turn, .. }
+            | State::EndTurn { turn, .. }
+            | State::CambioCall { turn, .. } => Some(turn),
+            _ => None,
+        }
+    }
+
+    /// Convert the timer of a waiting state,
+    /// `f` is given the timer and how long the state waits for.
+    pub(crate) fn map_timer<U>(
+        self,
+        timings: &Timings,
+        f: impl FnOnce(T, Duration) -> U,
+    ) -> State<U> {
+        match self {
+            State::Pregame => State::Pregame,
+            State::StartRound { round, reset_deck } => State::StartRound { round, reset_deck },
+            State::StartTurn { round, turn } => State::StartTurn { round, turn },
+            State::DrawCard { round, turn, card } => State::DrawCard { round, turn, card },
+            State::WaitingForDecision {
+                round,
+                turn,
+                card,
+                started,
+            } => State::WaitingForDecision {
+                round,
+                turn,
+                card,
+                started: f(started, timings.decision),
+            },
+            State::PlayDecision {
+                round,
+                turn,
+                card,
+                decision,
+            } => State::PlayDecision {
+                round,
+                turn,
+                card,
+                decision,
+            },
+            State::WaitingForSwap {
+                round,
+                turn,
+                own,
+                other,
+                started,
+            } => State::WaitingForSwap {
+                round,
+                turn,
+                own,
+                other,
+                started: f(started, timings.decision),
+            },
+            State::WaitingForSnaps {
+                round,
+                turn,
+                started,
+            } => State::WaitingForSnaps {
+                round,
+                turn,
+                started: f(started, timings.snap),
+            },
+            State::Snapped {
+                round,
+                turn,
+                snapper,
+                slot,
+                give,
+            } => State::Snapped {
+                round,
+                turn,
+                snapper,
+                slot,
+                give,
+            },
+            State::EndTurn { round, turn } => State::EndTurn { round, turn },
+            State::EndRound { round } => State::EndRound { round },
+            State::ShowingAll { round, started } => State::ShowingAll {
+                round,
+                started: f(started, timings.show_all),
+            },
+            State::FindWinner { round } => State::FindWinner { round },
+            State::WaitingForNewRound {
+                round,
+                confirmations,
+                started,
+            } => State::WaitingForNewRound {
+                round,
+                confirmations,
+                started: f(started, timings.new_round),
+            },
+            State::CambioCall {
+                round,
+                turn,
+                caller,
+            } => State::CambioCall {
+                round,
+                turn,
+                caller,
+            },
+            State::Finished => State::Finished,
+        }
+    }
+}
+
+/// Why a player's input was turned down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Rejection {
+    #[error("the game isn't waiting for that")]
+    NotWaiting,
+    #[error("it isn't your turn")]
+    NotYourTurn,
+    #[error("the drawn card doesn't allow that decision")]
+    NotAllowed,
+    #[error("the time to decide has run out")]
+    TooLate,
+}
+
+/// A player's place at the table, along with the cards in front of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Seat {
+    id: Uuid,
+    hand: Vec<Card>,
+    /// Who has seen each card in the hand.
+    seen: Vec<Vec<Uuid>>,
+    round_score: i32,
+    total: i32,
+    wins: usize,
+    eliminated: bool,
+}
+
+impl Seat {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn hand(&self) -> &[Card] {
+        &self.hand
+    }
+
+    pub fn score(&self, rules: &RuleSet) -> i32 {
+        self.hand.iter().map(|c| c.game_value(rules) as i32).sum()
+    }
+
+    /// Knocked out of the match, they no longer take turns.
+    pub fn is_eliminated(&self) -> bool {
+        self.eliminated
+    }
+
+    /// Who has seen each card, kept in step with the hand.
+    fn seen_mut(&mut self) -> &mut Vec<Vec<Uuid>> {
+        // cards put in the hand directly haven't been seen by anyone
+        self.seen.resize_with(self.hand.len(), Vec::new);
+        &mut self.seen
+    }
+
+    fn has_seen(&self, index: usize, id: Uuid) -> bool {
+        self.seen.get(index).is_some_and(|seen| seen.contains(&id))
+    }
+
+    fn reveal(&mut self, index: usize, id: Uuid) {
+        if let Some(seen) = self.seen_mut().get_mut(index) {
+            if !seen.contains(&id) {
+                seen.push(id);
+            }
+        }
+    }
+
+    /// Add a card nobody has seen to the end of the hand.
+    fn push(&mut self, card: Card) {
+        self.seen_mut().push(Vec::new());
+        self.hand.push(card);
+    }
+
+    /// Take the card at `index` out of the hand, along with who has seen it.
+    fn remove(&mut self, index: usize) -> (Card, Vec<Uuid>) {
+        let seen = self.seen_mut().remove(index);
+        (self.hand.remove(index), seen)
+    }
+
+    /// Put a card in place of the one at `index`, returning the old card and who had seen it.
+    fn replace(&mut self, index: usize, card: Card, seen: Vec<Uuid>) -> (Card, Vec<Uuid>) {
+        let seen = std::mem::replace(&mut self.seen_mut()[index], seen);
+        (std::mem::replace(&mut self.hand[index], card), seen)
+    }
+
+    fn clear(&mut self) {
+        self.hand.clear();
+        self.seen.clear();
+    }
+
+    fn standing(&self) -> Standing {
+        Standing {
+            id: self.id,
+            round: self.round_score,
+            total: self.total,
+            wins: self.wins,
+            eliminated: self.eliminated,
+        }
+    }
+}
+
+/// Cambio has been called, everyone else gets one more turn.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FinalLap {
+    caller: Uuid,
+    last_turn: usize,
+}
+
+/// Source of randomness for a [`Game`].
+///
+/// The same generator as [`rand::rngs::StdRng`], but it can be saved in a [`Snapshot`].
+pub type GameRng = rand_chacha::ChaCha12Rng;
+
+/// The whole table: the seats with their hands, the draw pile and the discard pile.
 pub struct Game {
-    pub deck: Deck,
+    rules: RuleSet,
+    timings: Timings,
+    seed: u64,
+    rng: GameRng,
+    deck: Deck,
+    discard: Vec<Card>,
+    seats: Vec<Seat>,
+    final_lap: Option<FinalLap>,
     state: State,
     events: VecDeque<Event>,
+    log: Option<replay::Log>,
 }
 
 impl Game {
     /// Advance the state of the game.
-    pub fn advance(&mut self) {
+    ///
+    /// Waiting periods are timed against `now`, rather than the system clock,
+    /// so that the game can be driven at any speed.
+    pub fn advance(&mut self, now: Instant) {
+        self.record(|log| Input::Advance { at: log.at(now) });
+        self.step(now);
+    }
+
+    /// Advance without recording it, for inputs that advance as part of what they do.
+    fn step(&mut self, now: Instant) {
         self.state = match self.state {
             State::Pregame => State::StartRound {
                 round: 0,
                 reset_deck: false,
             },
             State::StartRound { round, reset_deck } => {
-                self.output_event(Event::Setup);
+                self.output_event(Event::Setup(self.seed));
                 // reset and shuffle the cards each round
+                self.final_lap = None;
                 if reset_deck {
                     self.deck = Deck::full();
+                    self.discard.clear();
+                    for seat in &mut self.seats {
+                        seat.clear();
+                    }
+                }
+                self.deck.shuffle(&mut self.rng);
+
+                self.deal();
+                // everyone gets to look at some of their own cards
+                for seat in &mut self.seats {
+                    for index in 0..self.rules.first_peek {
+                        seat.reveal(index, seat.id);
+                    }
                 }
-                let mut rng = rand::thread_rng();
-                self.deck.shuffle(&mut rng);
+                self.output_event(Event::FirstDraw);
+                self.output_event(Event::FirstPeek);
                 // each round should start with a different player than the last
                 // offset the turn by the number of rounds
                 self.output_event(Event::StartRound(round));
@@ -114,10 +450,10 @@ impl Game {
             State::StartTurn { round, turn } => {
                 self.output_event(Event::StartTurn(turn));
 
-                if let Some(card) = self.deck.draw() {
+                if let Some(card) = self.draw() {
                     State::DrawCard { round, turn, card }
                 } else {
-                    // no more cards in deck
+                    // no more cards anywhere
                     State::EndRound { round }
                 }
             }
@@ -128,18 +464,21 @@ impl Game {
                 State::WaitingForDecision {
                     round,
                     turn,
-                    started: Instant::now(),
+                    card,
+                    started: now,
                 }
             }
             State::WaitingForDecision {
                 round,
                 turn,
+                card,
                 started,
             } => {
-                if started.elapsed() >= Self::MAX_DECISION_TIME {
+                if now.duration_since(started) >= self.timings.decision {
                     // waited too long to decide
-                    // player does nothing,
+                    // the drawn card is thrown away,
                     // end turn (no need to wait for snaps)
+                    self.discard(card);
                     State::EndTurn { round, turn }
                 } else {
                     // keep waiting
@@ -147,6 +486,7 @@ impl Game {
                     State::WaitingForDecision {
                         round,
                         turn,
+                        card,
                         started,
                     }
                 }
@@ -154,16 +494,55 @@ impl Game {
             State::PlayDecision {
                 round,
                 turn,
+                card,
                 decision,
             } => {
-                // TODO: actually play the decision
-                let _ = decision;
+                self.output_event(Event::PlayDecision(turn, decision));
 
-                self.output_event(Event::WaitForSnap);
-                State::WaitingForSnaps {
-                    round,
-                    turn,
-                    started: Instant::now(),
+                if let Some((own, other)) = self.play_decision(turn, card, decision) {
+                    // the player has seen both cards, let them choose to swap
+                    self.output_event(Event::WaitForSwap);
+                    State::WaitingForSwap {
+                        round,
+                        turn,
+                        own,
+                        other,
+                        started: now,
+                    }
+                } else {
+                    self.output_event(Event::WaitForSnap);
+                    State::WaitingForSnaps {
+                        round,
+                        turn,
+                        started: now,
+                    }
+                }
+            }
+            State::WaitingForSwap {
+                round,
+                turn,
+                own,
+                other,
+                started,
+            } => {
+                if now.duration_since(started) >= self.timings.decision {
+                    // waited too long to choose, keep the cards where they are
+                    self.output_event(Event::WaitForSnap);
+                    State::WaitingForSnaps {
+                        round,
+                        turn,
+                        started: now,
+                    }
+                } else {
+                    // keep waiting
+                    self.output_event(Event::WaitForSwap);
+                    State::WaitingForSwap {
+                        round,
+                        turn,
+                        own,
+                        other,
+                        started,
+                    }
                 }
             }
             State::WaitingForSnaps {
@@ -171,7 +550,7 @@ impl Game {
                 turn,
                 started,
             } => {
-                if started.elapsed() >= Self::MAX_SNAP_TIME {
+                if now.duration_since(started) >= self.timings.snap {
                     // waited too long for a snap
                     // let's end the turn
                     State::EndTurn { round, turn }
@@ -185,17 +564,27 @@ impl Game {
                     }
                 }
             }
-            State::Snapped { round, turn, card } => {
-                // TODO: actually snap the card
-                let _ = card;
+            State::Snapped {
+                round,
+                turn,
+                snapper,
+                slot,
+                give,
+            } => {
+                self.snap(snapper, slot, give);
 
                 State::EndTurn { round, turn }
             }
             State::EndTurn { round, turn } => {
                 self.output_event(Event::EndTurn(turn));
 
-                // keep playing this round whilst there are still cards left
-                if self.deck.is_empty() {
+                // everyone has had their last turn after cambio was called
+                let lap_over = self.final_lap.is_some_and(|lap| turn >= lap.last_turn);
+
+                // house rules might end the round once there are no cards left
+                let exhausted = self.rules.end_on_empty_deck && self.deck.is_empty();
+
+                if exhausted || lap_over {
                     State::EndRound { round }
                 } else {
                     let next_turn = turn + 1;
@@ -205,24 +594,49 @@ impl Game {
                     }
                 }
             }
-            State::CambioCall { round } => {
-                self.output_event(Event::Cambio);
-                State::EndRound { round }
+            State::CambioCall {
+                round,
+                turn,
+                caller,
+            } => {
+                self.output_event(Event::Cambio(caller));
+                // every other player gets exactly one more turn
+                self.final_lap = Some(FinalLap {
+                    caller,
+                    last_turn: turn + self.playing_count() - 1,
+                });
+                State::EndTurn { round, turn }
             }
             State::EndRound { round } => {
                 self.output_event(Event::EndRound(round));
-                State::FindWinner { round }
+                self.output_event(Event::ShowAll);
+                State::ShowingAll {
+                    round,
+                    started: now,
+                }
+            }
+            State::ShowingAll { round, started } => {
+                // give everyone a chance to see the cards
+                if now.duration_since(started) >= self.timings.show_all {
+                    State::FindWinner { round }
+                } else {
+                    State::ShowingAll { round, started }
+                }
             }
             State::FindWinner { round } => {
-                self.output_event(Event::FindWinner);
+                self.score_round();
 
-                // start at no confirmations
-                let confirmations = 0;
-                self.output_event(Event::WaitForNewRound { confirmations });
-                State::WaitingForNewRound {
-                    round,
-                    confirmations,
-                    started: Instant::now(),
+                if self.match_over(round) {
+                    self.finish_match()
+                } else {
+                    // start at no confirmations
+                    let confirmations = 0;
+                    self.output_event(Event::WaitForNewRound { confirmations });
+                    State::WaitingForNewRound {
+                        round,
+                        confirmations,
+                        started: now,
+                    }
                 }
             }
             State::WaitingForNewRound {
@@ -230,10 +644,10 @@ impl Game {
                 confirmations,
                 started,
             } => {
-                if started.elapsed() >= Self::MAX_NEW_ROUND_CONFIRM_TIME {
+                if now.duration_since(started) >= self.timings.new_round {
                     // waited too long for a new round
                     // let's end the game
-                    State::Finished
+                    self.finish_match()
                 } else {
                     // keep waiting otherwise...
                     self.output_event(Event::WaitForNewRound { confirmations });
@@ -254,18 +668,56 @@ impl Game {
 }
 
 impl Game {
+    /// Create a new [`Game`] in the [`State::Pregame`] state, with a random seed.
+    pub fn new(rules: RuleSet, timings: Timings) -> Self {
+        Self::new_with_seed(rules, timings, rand::random())
+    }
+
     /// Create a new [`Game`] in the [`State::Pregame`] state.
-    pub fn new() -> Self {
-        Self::new_with(Deck::full(), State::Pregame)
+    ///
+    /// Games with the same seed shuffle the deck the same way.
+    pub fn new_with_seed(rules: RuleSet, timings: Timings, seed: u64) -> Self {
+        Self::new_with(Deck::full(), State::Pregame, rules, timings, seed)
     }
 
-    /// Create a new [`Game`] from a [`Deck`] and [`State`].
-    pub fn new_with(deck: Deck, state: State) -> Self {
+    /// Create a new [`Game`] from a [`Deck`], [`State`], [`RuleSet`], [`Timings`] and seed.
+    pub fn new_with(deck: Deck, state: State, rules: RuleSet, timings: Timings, seed: u64) -> Self {
         Self {
+            rules,
+            timings,
+            seed,
+            rng: GameRng::seed_from_u64(seed),
             deck,
+            discard: Vec::new(),
+            seats: Vec::new(),
+            final_lap: None,
             state,
             events: VecDeque::new(),
+            log: None,
+        }
+    }
+
+    /// Take a seat at the table.
+    ///
+    /// Players can only join before the game has started.
+    /// Returns `false` if the player could not join.
+    pub fn join(&mut self, id: Uuid) -> bool {
+        self.record(|_| Input::Join(id));
+
+        if !matches!(self.state, State::Pregame) || self.seat_index(id).is_some() {
+            return false;
         }
+
+        self.seats.push(Seat {
+            id,
+            hand: Vec::with_capacity(self.rules.starting_cards),
+            seen: Vec::with_capacity(self.rules.starting_cards),
+            round_score: 0,
+            total: 0,
+            wins: 0,
+            eliminated: false,
+        });
+        true
     }
 
     /// Pop off an [`Event`], if there is any.
@@ -275,61 +727,179 @@ impl Game {
 
     /// Call Cambio!
     ///
+    /// Only the player whose turn it is can call, once per round.
+    /// Calling before playing the drawn card throws it away.
+    ///
     /// Returns `true` if the call was successful.
     /// If `false`, the game was in an invalid state to call cambio
-    pub fn cambio_call(&mut self) -> bool {
+    pub fn cambio_call(&mut self, caller: Uuid, called_at: Instant) -> bool {
+        self.record(|log| Input::CambioCall {
+            caller,
+            at: log.at(called_at),
+        });
+
+        let (round, turn) = match self.state {
+            State::WaitingForDecision { round, turn, .. }
+            | State::PlayDecision { round, turn, .. }
+            | State::WaitingForSwap { round, turn, .. }
+            | State::WaitingForSnaps { round, turn, .. } => (round, turn),
+            _ => return false,
+        };
+
+        if self.final_lap.is_some() || self.turn_id(turn) != caller {
+            return false;
+        }
+
         match self.state {
-            State::WaitingForDecision { round, .. } | State::WaitingForSnaps { round, .. } => {
-                self.state = State::CambioCall { round }
-            }
+            State::WaitingForDecision { card, .. } => self.discard(card),
             // PlayDecision changes deck state
             // so we have to advance first before calling cambio
-            State::PlayDecision { round, .. } => {
-                // only needs one advance, it always resolves
-                self.advance();
-                self.state = State::CambioCall { round };
+            State::PlayDecision { .. } => {
+                // only needs one advance, it always resolves,
+                // and replaying the call makes it again
+                self.step(called_at);
             }
-            _ => return false,
+            _ => (),
         }
+
+        self.state = State::CambioCall {
+            round,
+            turn,
+            caller,
+        };
         true
     }
 
-    const MAX_DECISION_TIME: Duration = Duration::from_secs(10);
-
-    /// A decision has been made whilst waiting.
+    /// A decision has been made by `id` whilst waiting.
     ///
-    /// Because we match on None, only the **first** decision is remembered.
-    pub fn handle_decision(&mut self, decision: Decision, decided_at: Instant) {
-        if let State::WaitingForDecision {
+    /// Only the **first** decision is remembered,
+    /// and it has to come from whoever's turn it is and be allowed by the drawn card.
+    pub fn handle_decision(
+        &mut self,
+        id: Uuid,
+        decision: Decision,
+        decided_at: Instant,
+    ) -> Result<(), Rejection> {
+        self.record(|log| Input::Decision {
+            id,
+            decision,
+            at: log.at(decided_at),
+        });
+
+        let State::WaitingForDecision {
             round,
             turn,
+            card,
             started,
         } = self.state
-        {
-            let elapsed = decided_at.duration_since(started);
+        else {
+            return Err(Rejection::NotWaiting);
+        };
 
-            if elapsed <= Self::MAX_DECISION_TIME {
-                self.state = State::PlayDecision {
-                    round,
-                    turn,
-                    decision,
-                }
-            } else {
-                // waited too long to decide
-                // player does nothing,
-                // end turn (no need to wait for snaps)
-                self.state = State::EndTurn { round, turn };
-            }
+        if self.turn_id(turn) != id {
+            return Err(Rejection::NotYourTurn);
+        }
+        if !decision.is_valid(valid_set(card, &self.rules)) {
+            return Err(Rejection::NotAllowed);
+        }
+
+        let elapsed = decided_at.duration_since(started);
+
+        if elapsed > self.timings.decision {
+            // waited too long to decide
+            // the drawn card is thrown away,
+            // end turn (no need to wait for snaps)
+            self.discard(card);
+            self.state = State::EndTurn { round, turn };
+            return Err(Rejection::TooLate);
         }
+
+        self.state = State::PlayDecision {
+            round,
+            turn,
+            card,
+            decision,
+        };
+        Ok(())
     }
 
-    const MAX_SNAP_TIME: Duration = Duration::from_secs(2);
+    /// The player `id` has looked at both cards and chosen whether to swap them.
+    ///
+    /// Only whoever's turn it is gets to choose.
+    pub fn handle_swap_choice(
+        &mut self,
+        id: Uuid,
+        swap: bool,
+        chosen_at: Instant,
+    ) -> Result<(), Rejection> {
+        self.record(|log| Input::SwapChoice {
+            id,
+            swap,
+            at: log.at(chosen_at),
+        });
+
+        let State::WaitingForSwap {
+            round,
+            turn,
+            own,
+            other,
+            started,
+        } = self.state
+        else {
+            return Err(Rejection::NotWaiting);
+        };
+
+        if self.turn_id(turn) != id {
+            return Err(Rejection::NotYourTurn);
+        }
+
+        let elapsed = chosen_at.duration_since(started);
+        // choosing too late is the same as not swapping
+        let in_time = elapsed <= self.timings.decision;
+
+        if swap && in_time {
+            let own = Slot {
+                player: id,
+                index: own,
+            };
+            self.swap(own, other);
+        }
+
+        self.output_event(Event::WaitForSnap);
+        self.state = State::WaitingForSnaps {
+            round,
+            turn,
+            started: chosen_at,
+        };
+
+        if in_time {
+            Ok(())
+        } else {
+            Err(Rejection::TooLate)
+        }
+    }
 
     /// Snap!
     ///
     /// We might've received it too late however,
     /// so we check if it's a valid snap first.
-    pub fn handle_snap(&mut self, card: Card, snapped_at: Instant) {
+    ///
+    /// Only the **first** snap matching the top of the discard pile wins,
+    /// anyone snapping a card that doesn't match draws a penalty card.
+    pub fn handle_snap(
+        &mut self,
+        snapper: Uuid,
+        slot: Slot,
+        give: Option<usize>,
+        snapped_at: Instant,
+    ) {
+        self.record(|log| Input::Snap {
+            snapper,
+            slot,
+            give,
+            at: log.at(snapped_at),
+        });
+
         if let State::WaitingForSnaps {
             round,
             turn,
@@ -338,9 +908,40 @@ impl Game {
         {
             let elapsed = snapped_at.duration_since(started);
 
-            if elapsed <= Self::MAX_SNAP_TIME {
-                // snap!
-                self.state = State::Snapped { round, turn, card };
+            if elapsed <= self.timings.snap {
+                let (Some(card), Some(top)) = (self.card_at(slot), self.top_discard()) else {
+                    // nothing to snap
+                    return;
+                };
+                if !self.is_playing(snapper) {
+                    // knocked out players are only watching
+                    return;
+                }
+                if slot.player != snapper && !self.rules.snap_opponents {
+                    // house rules say you can only snap your own cards
+                    return;
+                }
+
+                if card.same_rank(&top) {
+                    // snap!
+                    self.state = State::Snapped {
+                        round,
+                        turn,
+                        snapper,
+                        slot,
+                        give,
+                    };
+                } else {
+                    // it was turned over for everyone to see
+                    let ids = self.seats.iter().map(|seat| seat.id).collect::<Vec<_>>();
+                    if let Some(seat) = self.seat_index(slot.player) {
+                        for id in ids {
+                            self.seats[seat].reveal(slot.index, id);
+                        }
+                    }
+                    self.output_event(Event::WrongSnap(snapper, slot, card));
+                    self.penalise(snapper);
+                }
             } else {
                 // waited too long for a snap
                 // let's end the turn
@@ -349,10 +950,13 @@ impl Game {
         }
     }
 
-    const MAX_NEW_ROUND_CONFIRM_TIME: Duration = Duration::from_secs(10);
-
     /// Confirm a new round if we're waiting.
     pub fn confirm_new_round(&mut self, needed_confirms: usize, confirmed_at: Instant) {
+        self.record(|log| Input::ConfirmNewRound {
+            needed: needed_confirms,
+            at: log.at(confirmed_at),
+        });
+
         if let State::WaitingForNewRound {
             started,
             round,
@@ -364,7 +968,7 @@ impl Game {
             // increase confirmations and check if there are enough to move on
             *confirmations += 1;
 
-            if elapsed <= Self::MAX_NEW_ROUND_CONFIRM_TIME {
+            if elapsed <= self.timings.new_round {
                 if *confirmations >= needed_confirms {
                     // start a new round
                     let next_round = *round + 1;
@@ -377,48 +981,450 @@ impl Game {
             } else {
                 // waited too long for a new round
                 // let's end the game
-                self.state = State::Finished;
+                self.state = self.finish_match();
             }
         }
     }
 
     /// Skip playing another round.
     pub fn skip_new_round(&mut self) {
+        self.record(|_| Input::SkipNewRound);
+
         if let State::WaitingForNewRound { .. } = self.state {
-            self.state = State::Finished;
+            self.state = self.finish_match();
         }
     }
 
     /// Get the deadline for a waiting period.
     pub fn poll_wait_deadline(&self) -> Option<Instant> {
-        match self.state {
-            State::WaitingForDecision { started, .. } => Some(started + Self::MAX_DECISION_TIME),
-            State::WaitingForSnaps { started, .. } => Some(started + Self::MAX_SNAP_TIME),
-            State::WaitingForNewRound { started, .. } => {
-                Some(started + Self::MAX_NEW_ROUND_CONFIRM_TIME)
-            }
-            _ => None,
-        }
+        let mut deadline = None;
+        self.state.map_timer(&self.timings, |started, timeout| {
+            deadline = Some(started + timeout)
+        });
+        deadline
+    }
+
+    /// The house rules being played.
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// How long the game waits for players.
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// The seed used to shuffle the deck.
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
     pub fn current_state(&self) -> &State {
         &self.state
     }
 
-    fn output_event(&mut self, event: Event) {
-        self.events.push_back(event);
+    /// The players sat at the table, in turn order.
+    pub fn seats(&self) -> &[Seat] {
+        &self.seats
     }
-}
 
-impl Default for Game {
-    fn default() -> Self {
-        Self::new()
+    /// The id of the player whose go it is on this `turn`.
+    pub fn turn_id(&self, turn: usize) -> Uuid {
+        self.seats[self.turn_seat(turn)].id
     }
-}
 
-#[test]
+    /// The card at the top of the discard pile.
+    pub fn top_discard(&self) -> Option<Card> {
+        self.discard.last().copied()
+    }
+
+    /// Number of cards left to draw.
+    pub fn deck_len(&self) -> usize {
+        self.deck.len()
+    }
+
+    /// The table as the player `id` sees it.
+    ///
+    /// Cards are hidden unless `id` has seen them,
+    /// until everyone's hand is shown at the end of the round.
+    pub fn view(&self, id: Uuid) -> PlayerView {
+        let show_all = matches!(
+            self.state,
+            State::ShowingAll { .. }
+                | State::FindWinner { .. }
+                | State::WaitingForNewRound { .. }
+                | State::Finished
+        );
+
+        let hands = self
+            .seats
+            .iter()
+            .map(|seat| HandView {
+                id: seat.id,
+                cards: seat
+                    .hand
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &card)| (show_all || seat.has_seen(index, id)).then_some(card))
+                    .collect(),
+            })
+            .collect();
+
+        PlayerView {
+            id,
+            hands,
+            top_discard: self.top_discard(),
+            deck_len: self.deck_len(),
+        }
+    }
+
+    /// Everyone's score for the round, in seat order.
+    ///
+    /// Whoever called Cambio is penalised if their hand was not the lowest.
+    pub fn scores(&self) -> Vec<(Uuid, i32)> {
+        let playing = || self.seats.iter().filter(|seat| !seat.eliminated);
+        let lowest = playing()
+            .map(|seat| seat.score(&self.rules))
+            .min()
+            .unwrap_or_default();
+
+        playing()
+            .map(|seat| {
+                let mut score = seat.score(&self.rules);
+                if self.final_lap.is_some_and(|lap| lap.caller == seat.id) && score > lowest {
+                    score += self.rules.cambio_penalty;
+                }
+                (seat.id, score)
+            })
+            .collect()
+    }
+
+    /// The player with the lowest score for the round.
+    ///
+    /// Ties go to whoever has the fewest cards left,
+    /// and then to whoever sits earliest at the table.
+    ///
+    /// Returns `None` if nobody is playing.
+    pub fn winner(&self) -> Option<Uuid> {
+        self.scores()
+            .into_iter()
+            .min_by_key(|&(id, score)| {
+                let seat = self.seat_index(id).unwrap();
+                (score, self.seats[seat].hand.len())
+            })
+            .map(|(id, _)| id)
+    }
+
+    /// Everyone's place in the match, best first.
+    ///
+    /// Players still in the match come first, ordered by the lowest total.
+    /// Ties go to whoever has won the most rounds,
+    /// and then to whoever sits earliest at the table.
+    pub fn standings(&self) -> Vec<Standing> {
+        let mut standings = self.seats.iter().map(Seat::standing).collect::<Vec<_>>();
+        standings.sort_by_key(|s| (s.eliminated, s.total, std::cmp::Reverse(s.wins)));
+        standings
+    }
+
+    fn output_event(&mut self, event: Event) {
+        if let Some(log) = &mut self.log {
+            log.push(replay::Entry::Event(event.clone()));
+        }
+        self.events.push_back(event);
+    }
+
+    /// Seat of the player taking this `turn`, skipping anyone knocked out.
+    fn turn_seat(&self, turn: usize) -> usize {
+        let playing = (0..self.seats.len())
+            .filter(|&seat| !self.seats[seat].eliminated)
+            .collect::<Vec<_>>();
+
+        playing[turn % playing.len()]
+    }
+
+    /// Whether `id` is seated and still in the match.
+    fn is_playing(&self, id: Uuid) -> bool {
+        self.seat_index(id)
+            .is_some_and(|seat| !self.seats[seat].eliminated)
+    }
+
+    fn playing_count(&self) -> usize {
+        self.seats.iter().filter(|seat| !seat.eliminated).count()
+    }
+
+    /// Add the round's scores to everyone's totals, and knock out
+    /// anyone who has reached the points limit.
+    fn score_round(&mut self) {
+        let winner = self.winner();
+
+        for (id, score) in self.scores() {
+            let index = self.seat_index(id).unwrap();
+            let seat = &mut self.seats[index];
+            seat.round_score = score;
+            seat.total += score;
+            if winner == Some(id) {
+                seat.wins += 1;
+            }
+            if let MatchEnd::PointsLimit(limit) = self.rules.match_end {
+                seat.eliminated = seat.total >= limit;
+            }
+        }
+
+        if let Some(winner) = winner {
+            self.output_event(Event::FindWinner(winner));
+        }
+        self.output_event(Event::RoundScores(self.standings()));
+    }
+
+    fn match_over(&self, round: usize) -> bool {
+        match self.rules.match_end {
+            MatchEnd::Rounds(rounds) => round + 1 >= rounds,
+            MatchEnd::PointsLimit(_) => self.playing_count() <= 1,
+        }
+    }
+
+    fn finish_match(&mut self) -> State {
+        let standings = self.standings();
+        // there's nobody to win if everyone has left
+        if let Some(winner) = standings.first().map(|standing| standing.id) {
+            self.output_event(Event::MatchResult(winner, standings));
+        }
+        State::Finished
+    }
+
+    fn seat_index(&self, id: Uuid) -> Option<usize> {
+        self.seats.iter().position(|seat| seat.id == id)
+    }
+
+    fn card_at(&self, slot: Slot) -> Option<Card> {
+        let seat = self.seat_index(slot.player)?;
+        self.seats[seat].hand.get(slot.index).copied()
+    }
+
+    /// A card in someone else's hand, as seen by the player `id`.
+    fn opponent_card_at(&self, id: Uuid, slot: Slot) -> Option<Card> {
+        if slot.player == id {
+            return None;
+        }
+        self.card_at(slot)
+    }
+
+    /// A card in someone else's hand that can be swapped with.
+    ///
+    /// The hand of whoever called Cambio is locked.
+    fn swappable_card_at(&self, id: Uuid, slot: Slot) -> Option<Card> {
+        if self.is_locked(slot.player) {
+            return None;
+        }
+        self.opponent_card_at(id, slot)
+    }
+
+    fn is_locked(&self, id: Uuid) -> bool {
+        self.final_lap.is_some_and(|lap| lap.caller == id)
+    }
+
+    /// Deal the starting cards to everyone at the table.
+    fn deal(&mut self) {
+        for seat in self.seats.iter_mut().filter(|seat| !seat.eliminated) {
+            for _ in 0..self.rules.starting_cards {
+                if let Some(card) = self.deck.draw() {
+                    seat.push(card);
+                }
+            }
+        }
+    }
+
+    /// Draw from the deck, reshuffling the discard pile into it if it's run out.
+    fn draw(&mut self) -> Option<Card> {
+        if self.deck.is_empty() && !self.rules.end_on_empty_deck && self.discard.len() > 1 {
+            // the top card stays where it is, everything under it goes back in
+            let top = self.discard.pop();
+            self.deck.refill(self.discard.drain(..));
+            self.discard.extend(top);
+            self.deck.shuffle(&mut self.rng);
+            self.output_event(Event::Reshuffle);
+        }
+
+        self.deck.draw()
+    }
+
+    fn discard(&mut self, card: Card) {
+        self.discard.push(card);
+        self.output_event(Event::Discard(card));
+    }
+
+    /// Move the snapped card onto the discard pile.
+    ///
+    /// Snapping someone elses card lets the snapper fill the gap with one of their own,
+    /// otherwise the card is just taken out of the hand.
+    fn snap(&mut self, snapper: Uuid, slot: Slot, give: Option<usize>) {
+        let Some(card) = self.card_at(slot) else {
+            return;
+        };
+        let seat = self.seat_index(slot.player).unwrap();
+        let snapper_seat = self.seat_index(snapper).unwrap();
+
+        self.output_event(Event::Snapped(snapper, slot, card));
+
+        let given = give
+            .filter(|_| slot.player != snapper && !self.is_locked(slot.player))
+            .filter(|&index| index < self.seats[snapper_seat].hand.len());
+
+        if let Some(index) = given {
+            // whoever knew the gift still knows where it went
+            let (gift, seen) = self.seats[snapper_seat].remove(index);
+            self.seats[seat].replace(slot.index, gift, seen);
+
+            let from = Slot {
+                player: snapper,
+                index,
+            };
+            self.output_event(Event::GiveCard(from, slot));
+        } else {
+            self.seats[seat].remove(slot.index);
+        }
+
+        self.discard(card);
+    }
+
+    /// Draw a card from the deck into the hand of `id`, nobody gets to look at it.
+    fn penalise(&mut self, id: Uuid) {
+        let seat = self.seat_index(id).unwrap();
+
+        if let Some(card) = self.draw() {
+            self.seats[seat].push(card);
+            self.output_event(Event::Penalty(id));
+        }
+    }
+
+    fn swap(&mut self, a: Slot, b: Slot) {
+        let (Some(_), Some(y)) = (self.card_at(a), self.card_at(b)) else {
+            return;
+        };
+
+        // both slots exist, so both seats exist
+        let a_seat = self.seat_index(a.player).unwrap();
+        let b_seat = self.seat_index(b.player).unwrap();
+        // everyone saw the swap, so whoever knew a card still knows where it is
+        let (x, x_seen) = self.seats[a_seat].replace(a.index, y, Vec::new());
+        let (y, y_seen) = self.seats[b_seat].replace(b.index, x, x_seen);
+        self.seats[a_seat].replace(a.index, y, y_seen);
+
+        self.output_event(Event::Swap(a, b));
+    }
+
+    /// Show the player taking this `turn` the `card` in `slot`.
+    fn peek(&mut self, turn: usize, slot: Slot, card: Card) {
+        let id = self.turn_id(turn);
+        if let Some(seat) = self.seat_index(slot.player) {
+            self.seats[seat].reveal(slot.index, id);
+        }
+        self.output_event(Event::Peek(turn, slot, card));
+    }
+
+    /// Carry out the `decision` for the drawn `card`.
+    ///
+    /// Decisions the card does not allow, or that target cards which don't exist,
+    /// only discard the card.
+    ///
+    /// Returns the slots to choose to swap between for [`Decision::LookAndSwap`].
+    fn play_decision(
+        &mut self,
+        turn: usize,
+        card: Card,
+        decision: Decision,
+    ) -> Option<(usize, Slot)> {
+        let id = self.turn_id(turn);
+        let own = |index| Slot { player: id, index };
+
+        if !decision.is_valid(valid_set(card, &self.rules)) {
+            self.discard(card);
+            return None;
+        }
+
+        match decision {
+            Decision::Discard => self.discard(card),
+            Decision::Replace { own: index } => {
+                let seat = self.turn_seat(turn);
+                let seat = &mut self.seats[seat];
+                // they drew the card, so they know what they put down
+                let replaced = if index < seat.hand.len() {
+                    seat.replace(index, card, vec![id]).0
+                } else {
+                    card
+                };
+                self.discard(replaced);
+            }
+            Decision::LookAtOwn { own: index } => {
+                self.discard(card);
+                if let Some(seen) = self.card_at(own(index)) {
+                    self.peek(turn, own(index), seen);
+                }
+            }
+            Decision::LookAtOther { other } => {
+                self.discard(card);
+                if let Some(seen) = self.opponent_card_at(id, other) {
+                    self.peek(turn, other, seen);
+                }
+            }
+            Decision::BlindSwap { own: index, other } => {
+                self.discard(card);
+                if self.swappable_card_at(id, other).is_some() {
+                    self.swap(own(index), other);
+                }
+            }
+            Decision::LookAndSwap { own: index, other } => {
+                self.discard(card);
+                let mine = self.card_at(own(index));
+                let theirs = self.swappable_card_at(id, other);
+                if let (Some(mine), Some(theirs)) = (mine, theirs) {
+                    self.peek(turn, own(index), mine);
+                    self.peek(turn, other, theirs);
+                    return Some((index, other));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(RuleSet::default(), Timings::default())
+    }
+}
+
+#[cfg(test)]
+fn dealt_game(drawn: Card) -> (Game, Uuid, Uuid) {
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+    let mut game = Game::default();
+    game.join(a);
+    game.join(b);
+    // deal the cards
+    game.advance(Instant::now());
+    game.advance(Instant::now());
+    // pretend the first player has just drawn `drawn`
+    game.state = State::WaitingForDecision {
+        round: 0,
+        turn: 0,
+        card: drawn,
+        started: Instant::now(),
+    };
+
+    (game, a, b)
+}
+
+#[test]
 fn one_round_finish() {
-    let mut game = Game::new();
+    let mut game = Game::default();
+    game.join(Uuid::new_v4());
+    game.join(Uuid::new_v4());
+
+    let mut now = Instant::now();
+    let mut current = None;
+    let mut reshuffled = false;
 
     const MAX_ITERS: usize = 1000;
     for _ in 0..MAX_ITERS {
@@ -426,8 +1432,29 @@ fn one_round_finish() {
             println!("{:?}", event);
 
             match event {
-                Event::WaitForDecision => game.handle_decision(Decision::Replace, Instant::now()),
-                Event::WaitForSnap => game.handle_snap(Card::Joker, Instant::now()),
+                Event::StartTurn(turn) => current = Some(game.turn_id(turn)),
+                Event::Reshuffle => reshuffled = true,
+                // go round the deck once, then call it
+                Event::WaitForDecision if reshuffled && game.final_lap.is_none() => {
+                    game.cambio_call(current.unwrap(), now);
+                }
+                Event::WaitForDecision => game
+                    .handle_decision(current.unwrap(), Decision::Replace { own: 0 }, now)
+                    .unwrap(),
+                Event::WaitForSnap => {
+                    let top = game.top_discard().unwrap();
+                    let matching = game.seats().iter().find_map(|seat| {
+                        let index = seat.hand().iter().position(|c| c.same_rank(&top))?;
+                        Some(Slot {
+                            player: seat.id(),
+                            index,
+                        })
+                    });
+
+                    if let Some(slot) = matching {
+                        game.handle_snap(slot.player, slot, None, now);
+                    }
+                }
                 Event::WaitForNewRound { .. } => game.skip_new_round(),
                 Event::Exit => break,
                 _ => (),
@@ -437,18 +1464,987 @@ fn one_round_finish() {
             continue;
         }
 
-        // if there is a deadline, wait for it
+        // if there is a deadline, skip straight to it
         if let Some(deadline) = game.poll_wait_deadline() {
-            let now = Instant::now();
+            now = now.max(deadline);
+        }
+
+        // advance the game state
+        game.advance(now);
+    }
+
+    assert!(matches!(game.state, State::Finished));
+}
 
-            if let Some(delay) = deadline.checked_duration_since(now) {
-                std::thread::sleep(delay);
+#[test]
+fn timeouts_over_many_rounds() {
+    let rules = RuleSet {
+        match_end: MatchEnd::Rounds(10),
+        end_on_empty_deck: true,
+        ..RuleSet::default()
+    };
+    let mut game = Game::new_with_seed(rules, Timings::default(), 7);
+    let ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    for id in ids {
+        game.join(id);
+    }
+
+    let start = Instant::now();
+    let mut now = start;
+    let mut rounds = 0;
+
+    // nobody ever decides or snaps, but everyone wants another round, twice
+    while !matches!(game.state, State::Finished) {
+        while let Some(event) = game.poll_events() {
+            if let Event::WaitForNewRound { confirmations: 0 } = event {
+                rounds += 1;
+                if rounds < 3 {
+                    for _ in ids {
+                        game.confirm_new_round(ids.len(), now);
+                    }
+                }
             }
         }
 
-        // advance the game state
-        game.advance();
+        if let Some(deadline) = game.poll_wait_deadline() {
+            now = now.max(deadline);
+        }
+        game.advance(now);
     }
 
+    assert_eq!(rounds, 3);
+    // every turn of every round waited for the decision to time out
+    let turns_per_round = common::FULL_DECK.len() - ids.len() * common::STARTING_DECK_LEN;
+    // and showed everyone's hands at the end
+    let timings = Timings::default();
+    let waited =
+        timings.decision * (3 * turns_per_round) as u32 + timings.show_all * 3 + timings.new_round;
+    assert_eq!(now.duration_since(start), waited);
+}
+
+#[test]
+fn replace_keeps_drawn_card() {
+    let drawn = Card::Joker;
+    let (mut game, a, _) = dealt_game(drawn);
+    let replaced = game.seats[0].hand[2];
+
+    game.handle_decision(a, Decision::Replace { own: 2 }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+
+    assert_eq!(game.seats[0].hand[2], drawn);
+    assert_eq!(game.top_discard(), Some(replaced));
+    assert!(matches!(game.state, State::WaitingForSnaps { .. }));
+}
+
+#[test]
+fn blind_swap_changes_both_hands() {
+    let jack = Card::Normal {
+        suit: common::Suit::Spades,
+        face: common::Face::Jack,
+    };
+    let (mut game, a, b) = dealt_game(jack);
+    let (mine, theirs) = (game.seats[0].hand[0], game.seats[1].hand[3]);
+
+    let other = Slot {
+        player: b,
+        index: 3,
+    };
+    game.handle_decision(a, Decision::BlindSwap { own: 0, other }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+
+    assert_eq!(game.seats[0].hand[0], theirs);
+    assert_eq!(game.seats[1].hand[3], mine);
+    assert_eq!(game.top_discard(), Some(jack));
+
+    // can't swap with yourself
+    let (mut game, a, _) = dealt_game(jack);
+    let before = game.seats[0].hand.clone();
+
+    let other = Slot {
+        player: a,
+        index: 1,
+    };
+    game.handle_decision(a, Decision::BlindSwap { own: 0, other }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+
+    assert_eq!(game.seats[0].hand, before);
+}
+
+#[test]
+fn look_and_swap_waits_for_choice() {
+    let king = Card::Normal {
+        suit: common::Suit::Hearts,
+        face: common::Face::King,
+    };
+    let (mut game, a, b) = dealt_game(king);
+    let (mine, theirs) = (game.seats[0].hand[1], game.seats[1].hand[0]);
+
+    let other = Slot {
+        player: b,
+        index: 0,
+    };
+    game.handle_decision(a, Decision::LookAndSwap { own: 1, other }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+    assert!(matches!(game.state, State::WaitingForSwap { .. }));
+
+    let peeks = std::iter::from_fn(|| game.poll_events())
+        .filter_map(|event| match event {
+            Event::Peek(0, slot, card) => Some((slot, card)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let own = Slot {
+        player: a,
+        index: 1,
+    };
+    assert_eq!(peeks, [(own, mine), (other, theirs)]);
+
+    game.handle_swap_choice(a, true, Instant::now()).unwrap();
+    assert_eq!(game.seats[0].hand[1], theirs);
+    assert_eq!(game.seats[1].hand[0], mine);
+    assert!(matches!(game.state, State::WaitingForSnaps { .. }));
+}
+
+#[test]
+fn illegal_decisions_are_rejected() {
+    let two = Card::Normal {
+        suit: common::Suit::Clubs,
+        face: common::Face::Two,
+    };
+    let (mut game, a, b) = dealt_game(two);
+
+    let other = Slot {
+        player: b,
+        index: 0,
+    };
+    let swap = Decision::BlindSwap { own: 0, other };
+    assert_eq!(
+        game.handle_decision(a, swap, Instant::now()),
+        Err(Rejection::NotAllowed)
+    );
+    assert_eq!(
+        game.handle_decision(b, Decision::Discard, Instant::now()),
+        Err(Rejection::NotYourTurn)
+    );
+    assert_eq!(
+        game.handle_swap_choice(a, true, Instant::now()),
+        Err(Rejection::NotWaiting)
+    );
+    // still waiting for the real decision
+    assert!(matches!(game.state, State::WaitingForDecision { .. }));
+
+    let late = Instant::now() + game.timings.decision * 2;
+    assert_eq!(
+        game.handle_decision(a, Decision::Discard, late),
+        Err(Rejection::TooLate)
+    );
+    assert_eq!(game.top_discard(), Some(two));
+    assert!(matches!(game.state, State::EndTurn { .. }));
+}
+
+#[test]
+fn deal_conserves_cards() {
+    let mut game = Game::default();
+    for _ in 0..3 {
+        game.join(Uuid::new_v4());
+    }
+    // deal the cards
+    game.advance(Instant::now());
+    game.advance(Instant::now());
+
+    for seat in game.seats() {
+        assert_eq!(seat.hand().len(), common::STARTING_DECK_LEN);
+    }
+    let in_hands = game
+        .seats()
+        .iter()
+        .map(|seat| seat.hand().len())
+        .sum::<usize>();
+    assert_eq!(in_hands + game.deck_len(), common::FULL_DECK.len());
+    assert_eq!(game.top_discard(), None);
+}
+
+#[test]
+fn lowest_score_wins() {
+    let (mut game, a, _) = dealt_game(Card::Joker);
+    game.seats[0].hand = vec![Card::Joker; common::STARTING_DECK_LEN];
+    game.seats[1].hand = vec![
+        Card::Normal {
+            suit: common::Suit::Clubs,
+            face: common::Face::Ace,
+        };
+        common::STARTING_DECK_LEN
+    ];
+    assert_eq!(game.winner(), Some(a));
+
+    // ties go to the fewest cards, then the earliest seat
+    game.seats[1].hand = vec![Card::Joker; common::STARTING_DECK_LEN - 1];
+    assert_eq!(game.winner(), Some(game.seats[1].id));
+    game.seats[1].hand = vec![Card::Joker; common::STARTING_DECK_LEN];
+    assert_eq!(game.winner(), Some(a));
+}
+
+#[cfg(test)]
+fn waiting_for_snaps(top: Card) -> (Game, Uuid, Uuid) {
+    let (mut game, a, b) = dealt_game(top);
+    game.discard.push(top);
+    game.state = State::WaitingForSnaps {
+        round: 0,
+        turn: 0,
+        started: Instant::now(),
+    };
+
+    (game, a, b)
+}
+
+#[test]
+fn snap_own_card() {
+    let seven = |suit| Card::Normal {
+        suit,
+        face: common::Face::Seven,
+    };
+    let (mut game, _, b) = waiting_for_snaps(seven(common::Suit::Hearts));
+    game.seats[1].hand[2] = seven(common::Suit::Clubs);
+
+    let slot = Slot {
+        player: b,
+        index: 2,
+    };
+    game.handle_snap(b, slot, None, Instant::now());
+    game.advance(Instant::now());
+
+    assert_eq!(game.seats[1].hand.len(), common::STARTING_DECK_LEN - 1);
+    assert_eq!(game.top_discard(), Some(seven(common::Suit::Clubs)));
+    assert!(matches!(game.state, State::EndTurn { .. }));
+}
+
+#[test]
+fn wrong_snap_draws_penalty() {
+    let (mut game, a, _) = waiting_for_snaps(Card::Joker);
+    game.seats[0].hand[0] = Card::Normal {
+        suit: common::Suit::Diamonds,
+        face: common::Face::Four,
+    };
+    let deck_len = game.deck_len();
+
+    let slot = Slot {
+        player: a,
+        index: 0,
+    };
+    game.handle_snap(a, slot, None, Instant::now());
+
+    assert_eq!(game.seats[0].hand.len(), common::STARTING_DECK_LEN + 1);
+    assert_eq!(game.deck_len(), deck_len - 1);
+    assert_eq!(game.top_discard(), Some(Card::Joker));
+    // others can still snap
+    assert!(matches!(game.state, State::WaitingForSnaps { .. }));
+}
+
+#[test]
+fn wrong_snaps_are_seen_by_everyone() {
+    let (mut game, a, b) = waiting_for_snaps(Card::Joker);
+    let four = Card::Normal {
+        suit: common::Suit::Diamonds,
+        face: common::Face::Four,
+    };
+    game.seats[1].hand[2] = four;
+    assert_eq!(game.view(a).hands[1].cards[2], None);
+
+    let slot = Slot {
+        player: b,
+        index: 2,
+    };
+    game.handle_snap(b, slot, None, Instant::now());
+
+    // the card stays where it was, face down, but nobody forgets what it is
+    assert_eq!(game.view(a).hands[1].cards[2], Some(four));
+    assert_eq!(game.view(b).hands[1].cards[2], Some(four));
+}
+
+#[test]
+fn snap_opponent_card_and_give_one() {
+    let (mut game, a, b) = waiting_for_snaps(Card::Joker);
+    game.seats[1].hand[1] = Card::Joker;
+    let gift = game.seats[0].hand[3];
+
+    let slot = Slot {
+        player: b,
+        index: 1,
+    };
+    game.handle_snap(a, slot, Some(3), Instant::now());
+    // the first valid snap wins the race
+    game.handle_snap(b, slot, None, Instant::now());
+    game.advance(Instant::now());
+
+    assert_eq!(game.seats[0].hand.len(), common::STARTING_DECK_LEN - 1);
+    assert_eq!(game.seats[1].hand.len(), common::STARTING_DECK_LEN);
+    assert_eq!(game.seats[1].hand[1], gift);
+    assert_eq!(game.top_discard(), Some(Card::Joker));
+}
+
+#[test]
+fn cambio_gives_everyone_else_one_turn() {
+    let mut game = Game::default();
+    let ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    for id in ids {
+        game.join(id);
+    }
+    // deal the cards and start the first turn
+    while !matches!(game.state, State::WaitingForDecision { .. }) {
+        game.advance(Instant::now());
+    }
+
+    assert!(
+        !game.cambio_call(ids[1], Instant::now()),
+        "only the turn owner can call"
+    );
+    assert!(game.cambio_call(ids[0], Instant::now()));
+
+    let mut turns = Vec::new();
+    while !matches!(game.state, State::EndRound { .. }) {
+        if let State::WaitingForDecision { turn, .. } = game.state {
+            turns.push(game.turn_id(turn));
+            assert!(
+                !game.cambio_call(game.turn_id(turn), Instant::now()),
+                "cambio is called once"
+            );
+            game.handle_decision(game.turn_id(turn), Decision::Discard, Instant::now())
+                .unwrap();
+        }
+        if let State::WaitingForSnaps { round, turn, .. } = game.state {
+            game.state = State::EndTurn { round, turn };
+        }
+        game.advance(Instant::now());
+    }
+
+    assert_eq!(turns, [ids[1], ids[2]]);
+}
+
+#[test]
+fn cambio_caller_is_locked_and_penalised() {
+    let jack = Card::Normal {
+        suit: common::Suit::Hearts,
+        face: common::Face::Jack,
+    };
+    let (mut game, a, b) = dealt_game(jack);
+    game.final_lap = Some(FinalLap {
+        caller: a,
+        last_turn: 1,
+    });
+    game.state = State::WaitingForDecision {
+        round: 0,
+        turn: 1,
+        card: jack,
+        started: Instant::now(),
+    };
+    let before = game.seats[0].hand.clone();
+
+    let other = Slot {
+        player: a,
+        index: 0,
+    };
+    game.handle_decision(b, Decision::BlindSwap { own: 0, other }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+    assert_eq!(game.seats[0].hand, before);
+
+    game.seats[0].hand = vec![Card::Joker, jack];
+    game.seats[1].hand = vec![Card::Joker, Card::Joker];
+    assert_eq!(game.scores(), [(a, 11 + game.rules.cambio_penalty), (b, 0)]);
+    assert_eq!(game.winner(), Some(b));
+
+    // no penalty for tying with the lowest
+    game.seats[1].hand = vec![Card::Joker, jack];
+    assert_eq!(game.scores(), [(a, 11), (b, 11)]);
+    assert_eq!(game.winner(), Some(a));
+}
+
+#[cfg(test)]
+fn score_round_with(game: &mut Game, hands: [Vec<Card>; 2]) -> Vec<Event> {
+    for (seat, hand) in game.seats.iter_mut().zip(hands) {
+        seat.hand = hand;
+    }
+    game.state = State::FindWinner { round: 0 };
+    game.advance(Instant::now());
+
+    std::iter::from_fn(|| game.poll_events()).collect()
+}
+
+#[test]
+fn totals_add_up_over_rounds() {
+    let (mut game, a, b) = dealt_game(Card::Joker);
+    let king = Card::Normal {
+        suit: common::Suit::Spades,
+        face: common::Face::King,
+    };
+
+    let events = score_round_with(&mut game, [vec![king], vec![Card::Joker]]);
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::FindWinner(id) if *id == b)));
+    assert!(matches!(game.state, State::WaitingForNewRound { .. }));
+
+    score_round_with(&mut game, [vec![Card::Joker], vec![king, king]]);
+    let standings = game.standings();
+    assert_eq!(standings[0].id, a);
+    assert_eq!((standings[0].total, standings[0].wins), (13, 1));
+    assert_eq!((standings[1].total, standings[1].wins), (26, 1));
+    assert_eq!(standings[1].round, 26);
+}
+
+#[test]
+fn match_ends_after_the_last_round() {
+    let (mut game, a, _) = dealt_game(Card::Joker);
+    game.rules.match_end = MatchEnd::Rounds(1);
+
+    let events = score_round_with(&mut game, [vec![Card::Joker], vec![Card::Joker]]);
+    let Some(Event::MatchResult(winner, standings)) = events.last() else {
+        panic!("no match result in {events:?}");
+    };
+    assert_eq!(*winner, a);
+    assert_eq!(standings[0].id, a);
     assert!(matches!(game.state, State::Finished));
 }
+
+#[test]
+fn points_limit_knocks_players_out() {
+    let (mut game, a, b) = dealt_game(Card::Joker);
+    game.rules.match_end = MatchEnd::PointsLimit(20);
+    let king = Card::Normal {
+        suit: common::Suit::Clubs,
+        face: common::Face::King,
+    };
+
+    let events = score_round_with(&mut game, [vec![king, king], vec![Card::Joker]]);
+    let Some(Event::MatchResult(winner, standings)) = events.last() else {
+        panic!("no match result in {events:?}");
+    };
+    assert_eq!(*winner, b);
+    assert_eq!(standings[0].id, b);
+    assert_eq!(standings[1].id, a);
+    assert!(standings[1].eliminated);
+    assert!(game.seats[0].is_eliminated());
+}
+
+#[test]
+fn knocked_out_players_sit_out() {
+    let (mut game, _, b) = dealt_game(Card::Joker);
+    let c = Uuid::new_v4();
+    game.seats.push(Seat {
+        id: c,
+        hand: Vec::new(),
+        seen: Vec::new(),
+        round_score: 0,
+        total: 0,
+        wins: 0,
+        eliminated: false,
+    });
+    game.seats[0].eliminated = true;
+
+    assert_eq!(game.turn_id(0), b);
+    assert_eq!(game.turn_id(1), c);
+    assert_eq!(game.turn_id(2), b);
+    assert!(game.scores().iter().all(|&(id, _)| id != game.seats[0].id));
+}
+
+#[cfg(test)]
+fn dealt_hands(seed: u64) -> Vec<Vec<Card>> {
+    let mut game = Game::new_with_seed(RuleSet::default(), Timings::default(), seed);
+    game.join(Uuid::new_v4());
+    game.join(Uuid::new_v4());
+    // deal the cards
+    game.advance(Instant::now());
+    game.advance(Instant::now());
+
+    assert!(matches!(game.poll_events(), Some(Event::Setup(s)) if s == seed));
+    game.seats()
+        .iter()
+        .map(|seat| seat.hand().to_vec())
+        .collect()
+}
+
+#[test]
+fn seeded_deal_order() {
+    use common::{Face, Suit};
+
+    let card = |suit, face| Card::Normal { suit, face };
+
+    assert_eq!(dealt_hands(1234), dealt_hands(1234));
+    assert_ne!(dealt_hands(1234), dealt_hands(4321));
+
+    let expected = [
+        [
+            card(Suit::Hearts, Face::Ten),
+            card(Suit::Hearts, Face::Seven),
+            card(Suit::Spades, Face::Ace),
+            card(Suit::Hearts, Face::Five),
+        ],
+        [
+            card(Suit::Diamonds, Face::Jack),
+            card(Suit::Clubs, Face::Nine),
+            card(Suit::Diamonds, Face::Eight),
+            card(Suit::Clubs, Face::Seven),
+        ],
+    ];
+    assert_eq!(dealt_hands(1234), expected);
+}
+
+#[test]
+fn house_rules() {
+    use common::rules::Preset;
+
+    let mut game = Game::new(Preset::SixCards.rules(), Timings::default());
+    game.join(Uuid::new_v4());
+    game.join(Uuid::new_v4());
+    // deal the cards
+    game.advance(Instant::now());
+    game.advance(Instant::now());
+    for seat in game.seats() {
+        assert_eq!(seat.hand().len(), 6);
+    }
+
+    // no snapping other people's cards in a friendly game
+    let (mut game, a, b) = waiting_for_snaps(Card::Joker);
+    game.rules = Preset::Friendly.rules();
+    game.seats[1].hand[0] = Card::Joker;
+
+    let slot = Slot {
+        player: b,
+        index: 0,
+    };
+    game.handle_snap(a, slot, None, Instant::now());
+    assert!(matches!(game.state, State::WaitingForSnaps { .. }));
+    game.handle_snap(b, slot, None, Instant::now());
+    assert!(matches!(game.state, State::Snapped { .. }));
+}
+
+#[test]
+fn empty_deck_reshuffles_the_discard_pile() {
+    let (mut game, _, _) = dealt_game(Card::Joker);
+    let in_play = game.deck_len();
+    let seven = Card::Normal {
+        suit: common::Suit::Hearts,
+        face: common::Face::Seven,
+    };
+    while let Some(card) = game.deck.draw() {
+        game.discard.push(card);
+    }
+    game.discard.push(seven);
+    game.state = State::StartTurn { round: 0, turn: 1 };
+    game.advance(Instant::now());
+
+    assert!(std::iter::from_fn(|| game.poll_events()).any(|e| matches!(e, Event::Reshuffle)));
+    assert!(matches!(game.state, State::DrawCard { .. }));
+    assert_eq!(game.top_discard(), Some(seven));
+    assert_eq!(game.discard.len(), 1);
+    assert_eq!(game.deck_len(), in_play - 1);
+}
+
+#[test]
+fn empty_deck_can_end_the_round() {
+    let (mut game, _, _) = dealt_game(Card::Joker);
+    game.rules.end_on_empty_deck = true;
+    while let Some(card) = game.deck.draw() {
+        game.discard.push(card);
+    }
+    game.state = State::EndTurn { round: 0, turn: 0 };
+    game.advance(Instant::now());
+
+    assert!(matches!(game.state, State::EndRound { .. }));
+    assert!(game.discard.len() > 1);
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let mut game = Game::new_with_seed(RuleSet::default(), Timings::default(), 99);
+    game.join(Uuid::new_v4());
+    game.join(Uuid::new_v4());
+
+    let start = Instant::now();
+    while !matches!(game.state, State::WaitingForDecision { .. }) {
+        game.advance(start);
+    }
+
+    // three seconds into the decision
+    let now = start + Duration::from_secs(3);
+    let json = serde_json::to_string(&game.snapshot(now)).unwrap();
+    let later = now + Duration::from_secs(60);
+    let mut restored = Game::restore(serde_json::from_str(&json).unwrap(), later);
+
+    let left = Timings::default().decision - Duration::from_secs(3);
+    assert_eq!(restored.poll_wait_deadline(), Some(later + left));
+
+    // both games carry on the same way, down to the shuffles
+    let hands = |game: &Game| {
+        game.seats()
+            .iter()
+            .map(|seat| seat.hand().to_vec())
+            .collect::<Vec<_>>()
+    };
+    for step in 1..500 {
+        let wait = Duration::from_secs(60) * step;
+        game.advance(now + wait);
+        restored.advance(later + wait);
+        assert_eq!(hands(&restored), hands(&game));
+        assert_eq!(restored.top_discard(), game.top_discard());
+    }
+}
+
+#[test]
+fn replay_rebuilds_a_recorded_game() {
+    let mut game = Game::new_with_seed(RuleSet::default(), Timings::default(), 5);
+    let start = Instant::now();
+    assert!(game.start_log(start));
+    let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+    game.join(a);
+    game.join(b);
+
+    // a few turns of replacing, then a snap, then Cambio called mid-decision
+    let mut now = start;
+    let mut lines = Vec::new();
+    let mut snapped = false;
+    let mut called = false;
+    for step in 0..40 {
+        now += Duration::from_millis(300);
+        match game.state {
+            State::WaitingForDecision { turn, .. } => {
+                let id = game.turn_id(turn);
+                game.handle_decision(id, Decision::Replace { own: step % 2 }, now)
+                    .unwrap();
+                if snapped && !called {
+                    assert!(game.cambio_call(id, now));
+                    called = true;
+                }
+            }
+            State::WaitingForSnaps { .. } if !snapped => {
+                let slot = Slot {
+                    player: b,
+                    index: 0,
+                };
+                game.handle_snap(b, slot, None, now);
+                snapped = true;
+            }
+            _ => game.advance(now),
+        }
+        while game.poll_events().is_some() {}
+        for entry in game.drain_log() {
+            replay::append(&mut lines, &entry).unwrap();
+        }
+    }
+
+    let entries = replay::read(&lines[..]).unwrap();
+    let mut rebuilt = replay::replay(&entries, entries.len()).unwrap();
+    let hands = |game: &Game| {
+        game.seats()
+            .iter()
+            .map(|seat| seat.hand().to_vec())
+            .collect::<Vec<_>>()
+    };
+    assert!(called);
+    assert_eq!(hands(&rebuilt), hands(&game));
+    assert_eq!(rebuilt.top_discard(), game.top_discard());
+    assert_eq!(
+        std::mem::discriminant(&rebuilt.state),
+        std::mem::discriminant(&game.state)
+    );
+    assert_eq!(
+        rebuilt.poll_wait_deadline().is_some(),
+        game.poll_wait_deadline().is_some()
+    );
+
+    // the rebuilt game emits exactly what was recorded
+    let recorded = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            replay::Entry::Event(event) => Some(format!("{event:?}")),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let emitted = std::iter::from_fn(|| rebuilt.poll_events())
+        .map(|event| format!("{event:?}"))
+        .collect::<Vec<_>>();
+    assert_eq!(emitted, recorded);
+
+    // and can be rebuilt part of the way through
+    let early = replay::replay(&entries, 3).unwrap();
+    assert_eq!(early.seats().len(), 2);
+    assert!(matches!(early.state, State::Pregame));
+
+    assert!(matches!(
+        replay::replay(&entries[1..], 1),
+        Err(replay::ReplayError::MissingStart)
+    ));
+}
+
+#[test]
+fn views_only_show_seen_cards() {
+    let ten = Card::Normal {
+        suit: common::Suit::Diamonds,
+        face: common::Face::Ten,
+    };
+    let (mut game, a, b) = dealt_game(ten);
+    let cards = |view: &PlayerView, seat: usize| view.hands[seat].cards.clone();
+    let hand = |game: &Game, seat: usize| game.seats[seat].hand.clone();
+
+    // only the first peek of your own hand
+    let view = game.view(a);
+    let (own, theirs) = (hand(&game, 0), hand(&game, 1));
+    assert_eq!(cards(&view, 0), [Some(own[0]), Some(own[1]), None, None]);
+    assert_eq!(cards(&view, 1), [None; 4]);
+    assert_eq!(view.own_hand().unwrap().id, a);
+    assert_eq!(view.deck_len, game.deck_len());
+
+    // looking at someone else's card only shows it to you
+    let other = Slot {
+        player: b,
+        index: 2,
+    };
+    game.handle_decision(a, Decision::LookAtOther { other }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+    assert_eq!(cards(&game.view(a), 1)[2], Some(theirs[2]));
+    assert_eq!(cards(&game.view(b), 1)[2], None);
+    assert_eq!(game.view(b).top_discard, Some(ten));
+
+    // a blind swap moves what you know along with the card
+    let queen = Card::Normal {
+        suit: common::Suit::Hearts,
+        face: common::Face::Queen,
+    };
+    game.state = State::WaitingForDecision {
+        round: 0,
+        turn: 0,
+        card: queen,
+        started: Instant::now(),
+    };
+    game.handle_decision(a, Decision::BlindSwap { own: 0, other }, Instant::now())
+        .unwrap();
+    game.advance(Instant::now());
+    let view = game.view(a);
+    assert_eq!(cards(&view, 0)[0], Some(theirs[2]));
+    assert_eq!(cards(&view, 1)[2], Some(own[0]));
+    assert_eq!(cards(&game.view(b), 1)[2], None);
+
+    // everything is shown at the end of the round
+    game.state = State::ShowingAll {
+        round: 0,
+        started: Instant::now(),
+    };
+    assert!(game
+        .view(b)
+        .hands
+        .iter()
+        .flat_map(|hand| &hand.cards)
+        .all(Option::is_some));
+}
+
+/// Something a player (or the clock) does to the game, by seat number.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+enum Step {
+    Wait(u64),
+    Decide {
+        player: usize,
+        kind: common::decisions::DecisionKind,
+        own: usize,
+        other: (usize, usize),
+    },
+    ChooseSwap {
+        player: usize,
+        swap: bool,
+    },
+    Snap {
+        snapper: usize,
+        target: (usize, usize),
+        give: Option<usize>,
+    },
+    Cambio(usize),
+    Confirm,
+    Skip,
+}
+
+#[cfg(test)]
+fn step() -> impl proptest::strategy::Strategy<Value = Step> {
+    use common::decisions::DecisionKind;
+    use proptest::prelude::*;
+
+    let player = 0..8usize;
+    let index = 0..8usize;
+    prop_oneof![
+        4 => (0..20_000u64).prop_map(Step::Wait),
+        3 => (
+            player.clone(),
+            prop::sample::select(DecisionKind::ALL.to_vec()),
+            index.clone(),
+            (player.clone(), index.clone()),
+        )
+            .prop_map(|(player, kind, own, other)| Step::Decide {
+                player,
+                kind,
+                own,
+                other,
+            }),
+        1 => (player.clone(), any::<bool>())
+            .prop_map(|(player, swap)| Step::ChooseSwap { player, swap }),
+        2 => (
+            player.clone(),
+            (player.clone(), index.clone()),
+            prop::option::of(index),
+        )
+            .prop_map(|(snapper, target, give)| Step::Snap {
+                snapper,
+                target,
+                give,
+            }),
+        1 => player.prop_map(Step::Cambio),
+        1 => Just(Step::Confirm),
+        1 => Just(Step::Skip),
+    ]
+}
+
+#[cfg(test)]
+fn apply(game: &mut Game, step: &Step, ids: &[Uuid], now: &mut Instant) {
+    use common::decisions::DecisionKind;
+
+    let id = |player: usize| ids[player % ids.len()];
+    let slot = |(player, index): (usize, usize)| Slot {
+        player: id(player),
+        index,
+    };
+
+    match *step {
+        Step::Wait(millis) => *now += std::time::Duration::from_millis(millis),
+        Step::Decide {
+            player,
+            kind,
+            own,
+            other,
+        } => {
+            let other = slot(other);
+            let decision = match kind {
+                DecisionKind::Discard => Decision::Discard,
+                DecisionKind::Replace => Decision::Replace { own },
+                DecisionKind::LookAtOwn => Decision::LookAtOwn { own },
+                DecisionKind::LookAtOther => Decision::LookAtOther { other },
+                DecisionKind::BlindSwap => Decision::BlindSwap { own, other },
+                DecisionKind::LookAndSwap => Decision::LookAndSwap { own, other },
+            };
+            let _ = game.handle_decision(id(player), decision, *now);
+        }
+        Step::ChooseSwap { player, swap } => {
+            let _ = game.handle_swap_choice(id(player), swap, *now);
+        }
+        Step::Snap {
+            snapper,
+            target,
+            give,
+        } => game.handle_snap(id(snapper), slot(target), give, *now),
+        Step::Cambio(player) => {
+            game.cambio_call(id(player), *now);
+        }
+        Step::Confirm => game.confirm_new_round(ids.len(), *now),
+        Step::Skip => game.skip_new_round(),
+    }
+}
+
+#[cfg(test)]
+fn check_invariants(game: &Game) {
+    // every card is somewhere, including the one that's just been drawn
+    let drawn = match game.state {
+        State::DrawCard { .. } | State::WaitingForDecision { .. } | State::PlayDecision { .. } => 1,
+        _ => 0,
+    };
+    let in_hands = game.seats.iter().map(|seat| seat.hand.len()).sum::<usize>();
+    assert_eq!(
+        game.deck_len() + game.discard.len() + in_hands + drawn,
+        common::FULL_DECK.len(),
+        "cards went missing in {:?}",
+        game.state
+    );
+
+    // nobody is sat at the table twice
+    let ids = game
+        .seats
+        .iter()
+        .map(|seat| seat.id)
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(ids.len(), game.seats.len());
+
+    // the turn always belongs to a player who's still in the match
+    if let State::StartTurn { turn, .. }
+    | State::WaitingForDecision { turn, .. }
+    | State::WaitingForSwap { turn, .. }
+    | State::WaitingForSnaps { turn, .. }
+    | State::EndTurn { turn, .. } = game.state
+    {
+        let playing = game
+            .seats
+            .iter()
+            .filter(|seat| !seat.eliminated)
+            .collect::<Vec<_>>();
+        assert!(!playing.is_empty(), "nobody left to take turn {turn}");
+        assert_eq!(game.turn_id(turn), playing[turn % playing.len()].id);
+
+        // nobody plays past the final lap, and the caller sits it out
+        if let Some(lap) = game.final_lap {
+            assert!(turn <= lap.last_turn, "turn {turn} is past the final lap");
+            if !matches!(game.state, State::EndTurn { .. }) {
+                assert_ne!(
+                    game.turn_id(turn),
+                    lap.caller,
+                    "the caller took another turn"
+                );
+            }
+        }
+    }
+
+    // whoever called Cambio is sat at the table
+    if let Some(lap) = game.final_lap {
+        assert!(game.seat_index(lap.caller).is_some());
+    }
+
+    // everyone sees every hand, even if not every card in it
+    for seat in &game.seats {
+        let view = game.view(seat.id);
+        for (hand, seat) in view.hands.iter().zip(&game.seats) {
+            assert_eq!(hand.cards.len(), seat.hand.len());
+        }
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn random_inputs_keep_invariants(
+        players in 2..=4usize,
+        preset in proptest::sample::select(common::rules::Preset::ALL.to_vec()),
+        seed: u64,
+        steps in proptest::collection::vec(step(), 1..400),
+    ) {
+        let mut game = Game::new_with_seed(preset.rules(), Timings::default(), seed);
+        let ids = (0..players).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+        for &id in &ids {
+            game.join(id);
+        }
+
+        let mut now = Instant::now();
+        let mut finished = false;
+        for step in &steps {
+            apply(&mut game, step, &ids, &mut now);
+            game.advance(now);
+            while game.poll_events().is_some() {}
+
+            check_invariants(&game);
+
+            // nothing brings a finished game back
+            if finished {
+                proptest::prop_assert!(matches!(game.state, State::Finished));
+            }
+            finished = matches!(game.state, State::Finished);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Recording games as they're played, and rebuilding them from the recording.
+//!
+//! A log is a list of [`Entry`]s, stored one JSON object per line.
+//! It starts with how the game was set up, then every input the game was given
+//! along with every event it emitted, in the order they happened.
+//!
+//! Inputs are timed from when recording started,
+//! so replaying them gives back the exact same game.
+
+use std::{
+    io::{self, BufRead, Write},
+    time::{Duration, Instant},
+};
+
+use common::{
+    decisions::{Decision, Slot},
+    rules::RuleSet,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{Event, Game, Timings};
+
+/// A single line in a game log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Entry {
+    /// How the game was set up when recording started.
+    Start {
+        seed: u64,
+        rules: RuleSet,
+        timings: Timings,
+        players: Vec<Uuid>,
+    },
+    /// Something the game was told to do.
+    Input(Input),
+    /// Something the game emitted.
+    Event(Event),
+}
+
+/// A call into the [`Game`], with the time it was made.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Input {
+    Join(Uuid),
+    Advance {
+        at: Duration,
+    },
+    CambioCall {
+        caller: Uuid,
+        at: Duration,
+    },
+    Decision {
+        id: Uuid,
+        decision: Decision,
+        at: Duration,
+    },
+    SwapChoice {
+        id: Uuid,
+        swap: bool,
+        at: Duration,
+    },
+    Snap {
+        snapper: Uuid,
+        slot: Slot,
+        give: Option<usize>,
+        at: Duration,
+    },
+    ConfirmNewRound {
+        needed: usize,
+        at: Duration,
+    },
+    SkipNewRound,
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("log doesn't start with the game setup")]
+    MissingStart,
+    #[error("invalid entry on line {line}: {error}")]
+    Parse {
+        line: usize,
+        error: serde_json::Error,
+    },
+    #[error("failed to read log: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// The entries recorded so far, timed from `started`.
+pub(crate) struct Log {
+    started: Instant,
+    entries: Vec<Entry>,
+}
+
+impl Log {
+    pub(crate) fn push(&mut self, entry: Entry) {
+        self.entries.push(entry);
+    }
+
+    /// Time since recording started.
+    pub(crate) fn at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.started)
+    }
+}
+
+impl Game {
+    /// Start recording everything that happens to the game, timed from `now`.
+    ///
+    /// Only games that haven't started yet can be recorded.
+    /// Returns `false` if the game has already started.
+    pub fn start_log(&mut self, now: Instant) -> bool {
+        if !matches!(self.state, crate::State::Pregame) {
+            return false;
+        }
+
+        let start = Entry::Start {
+            seed: self.seed,
+            rules: self.rules.clone(),
+            timings: self.timings,
+            players: self.seats.iter().map(|seat| seat.id).collect(),
+        };
+        self.log = Some(Log {
+            started: now,
+            entries: vec![start],
+        });
+
+        true
+    }
+
+    /// Take the entries recorded since the last call.
+    pub fn drain_log(&mut self) -> impl Iterator<Item = Entry> + '_ {
+        self.log.iter_mut().flat_map(|log| log.entries.drain(..))
+    }
+
+    pub(crate) fn record(&mut self, input: impl FnOnce(&Log) -> Input) {
+        if let Some(log) = &mut self.log {
+            let input = input(log);
+            log.push(Entry::Input(input));
+        }
+    }
+}
+
+/// Rebuild the game as it was after the first `step` entries of the log.
+///
+/// Events in the log are skipped, the rebuilt game emits them again by itself.
+pub fn replay(entries: &[Entry], step: usize) -> Result<Game, ReplayError> {
+    let Some(Entry::Start {
+        seed,
+        rules,
+        timings,
+        players,
+    }) = entries.first()
+    else {
+        return Err(ReplayError::MissingStart);
+    };
+
+    let mut game = Game::new_with_seed(rules.clone(), *timings, *seed);
+    for &id in players {
+        game.join(id);
+    }
+
+    // inputs only need to be timed relative to each other
+    let started = Instant::now();
+    for entry in entries.iter().take(step).skip(1) {
+        let Entry::Input(input) = entry else {
+            continue;
+        };
+
+        match *input {
+            Input::Join(id) => {
+                game.join(id);
+            }
+            Input::Advance { at } => game.advance(started + at),
+            Input::CambioCall { caller, at } => {
+                game.cambio_call(caller, started + at);
+            }
+            Input::Decision { id, decision, at } => {
+                let _ = game.handle_decision(id, decision, started + at);
+            }
+            Input::SwapChoice { id, swap, at } => {
+                let _ = game.handle_swap_choice(id, swap, started + at);
+            }
+            Input::Snap {
+                snapper,
+                slot,
+                give,
+                at,
+            } => game.handle_snap(snapper, slot, give, started + at),
+            Input::ConfirmNewRound { needed, at } => game.confirm_new_round(needed, started + at),
+            Input::SkipNewRound => game.skip_new_round(),
+        }
+    }
+
+    Ok(game)
+}
+
+/// Read a log, one entry per line.
+pub fn read(reader: impl BufRead) -> Result<Vec<Entry>, ReplayError> {
+    reader
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(&line?).map_err(|error| ReplayError::Parse {
+                line: index + 1,
+                error,
+            })
+        })
+        .collect()
+}
+
+/// Append an entry to a log.
+pub fn append(writer: &mut impl Write, entry: &Entry) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, entry)?;
+    writer.write_all(b"\n")
+}
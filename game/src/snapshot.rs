@@ -0,0 +1,74 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use common::{rules::RuleSet, Card, Deck};
+use serde::{Deserialize, Serialize};
+
+use crate::{Event, FinalLap, Game, GameRng, Seat, State, Timings};
+
+/// Everything needed to pick a [`Game`] back up where it left off.
+///
+/// Timers are saved as the time they had left,
+/// so a restored game carries on waiting for just as long.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    rules: RuleSet,
+    timings: Timings,
+    seed: u64,
+    rng: GameRng,
+    deck: Deck,
+    discard: Vec<Card>,
+    seats: Vec<Seat>,
+    final_lap: Option<FinalLap>,
+    state: State<Duration>,
+    events: VecDeque<Event>,
+}
+
+impl Game {
+    /// Save the whole game as it is at `now`.
+    pub fn snapshot(&self, now: Instant) -> Snapshot {
+        let state = self.state.map_timer(&self.timings, |started, timeout| {
+            timeout.saturating_sub(now.saturating_duration_since(started))
+        });
+
+        Snapshot {
+            rules: self.rules.clone(),
+            timings: self.timings,
+            seed: self.seed,
+            rng: self.rng.clone(),
+            deck: self.deck.clone(),
+            discard: self.discard.clone(),
+            seats: self.seats.clone(),
+            final_lap: self.final_lap,
+            state,
+            events: self.events.clone(),
+        }
+    }
+
+    /// Carry on from a [`Snapshot`], with its timers restarting from `now`.
+    pub fn restore(snapshot: Snapshot, now: Instant) -> Self {
+        let state = snapshot
+            .state
+            .map_timer(&snapshot.timings, |remaining, timeout| {
+                // pretend we started waiting long enough ago to have `remaining` left
+                let waited = timeout.saturating_sub(remaining);
+                now.checked_sub(waited).unwrap_or(now)
+            });
+
+        Self {
+            rules: snapshot.rules,
+            timings: snapshot.timings,
+            seed: snapshot.seed,
+            rng: snapshot.rng,
+            deck: snapshot.deck,
+            discard: snapshot.discard,
+            seats: snapshot.seats,
+            final_lap: snapshot.final_lap,
+            state,
+            events: snapshot.events,
+            log: None,
+        }
+    }
+}
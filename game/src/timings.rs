@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How long the game waits for players before moving on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Time the player has to decide what to do with their card,
+    /// and whether to swap after looking.
+    pub decision: Duration,
+    /// Time everyone has to snap after a card is played.
+    pub snap: Duration,
+    /// Time everyone has to confirm they want another round.
+    pub new_round: Duration,
+    /// Time everyone's hands are shown before the winner is announced.
+    pub show_all: Duration,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            decision: Duration::from_secs(10),
+            snap: Duration::from_secs(2),
+            new_round: Duration::from_secs(10),
+            show_all: Duration::from_secs(1),
+        }
+    }
+}
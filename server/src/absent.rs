@@ -0,0 +1,197 @@
+//! Players who dropped out mid-game.
+//!
+//! Their seat is kept for them, with their turns skipped,
+//! until the grace period runs out and a bot takes over.
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use client::StrategyKind;
+use common::decisions::Decision;
+use game::{Event, Game, State};
+use uuid::Uuid;
+
+pub struct Absent {
+    /// When each of them dropped out.
+    since: HashMap<Uuid, Instant>,
+    grace: Duration,
+    /// How the bot that takes over plays.
+    stand_in: StrategyKind,
+}
+
+impl Absent {
+    pub fn new(grace: Duration, stand_in: StrategyKind) -> Self {
+        Self {
+            since: HashMap::new(),
+            grace,
+            stand_in,
+        }
+    }
+
+    /// How the bot taking over for someone who didn't come back plays.
+    pub fn stand_in(&self) -> StrategyKind {
+        self.stand_in
+    }
+
+    /// Player `id` dropped out at `now`.
+    pub fn left(&mut self, id: Uuid, now: Instant) {
+        self.since.entry(id).or_insert(now);
+    }
+
+    /// Player `id` is back, returns `false` if they weren't missing.
+    pub fn returned(&mut self, id: Uuid) -> bool {
+        self.since.remove(&id).is_some()
+    }
+
+    /// Everyone who has been gone longer than the grace period, they lose their seat.
+    pub fn expired(&mut self, now: Instant) -> Vec<Uuid> {
+        let expired = self
+            .since
+            .iter()
+            .filter(|&(_, &since)| now.saturating_duration_since(since) >= self.grace)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+
+        for id in &expired {
+            self.since.remove(id);
+        }
+        expired
+    }
+
+    /// Skip anything the game is waiting on an absent player for, after `event`.
+    ///
+    /// Anyone confirmed for is added to `confirmed`, so they can't confirm again if they rejoin.
+    pub fn skip(
+        &self,
+        game: &mut Game,
+        event: &Event,
+        confirmed: &mut HashSet<Uuid>,
+        now: Instant,
+    ) {
+        if self.since.is_empty() {
+            return;
+        }
+
+        match event {
+            Event::WaitForDecision | Event::WaitForSwap => self.skip_turn(game, now),
+            Event::WaitForNewRound { confirmations: 0 } => {
+                // nobody should be held up waiting for them
+                let needed = game.seats().len();
+                for &id in self.since.keys() {
+                    if confirmed.insert(id) {
+                        game.confirm_new_round(needed, now);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Play out the turn of an absent player, if the game is waiting on them.
+    pub fn skip_turn(&self, game: &mut Game, now: Instant) {
+        let missing = |turn| Some(game.turn_id(turn)).filter(|id| self.since.contains_key(id));
+
+        match *game.current_state() {
+            State::WaitingForDecision { turn, .. } => {
+                if let Some(id) = missing(turn) {
+                    let _ = game.handle_decision(id, Decision::Discard, now);
+                }
+            }
+            State::WaitingForSwap { turn, .. } => {
+                if let Some(id) = missing(turn) {
+                    let _ = game.handle_swap_choice(id, false, now);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[test]
+fn absent_turns_are_skipped_until_the_grace_period_ends() {
+    let (here, gone) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut game = Game::default();
+    game.join(here);
+    game.join(gone);
+
+    let start = Instant::now();
+    let mut absent = Absent::new(Duration::from_secs(60), StrategyKind::default());
+    absent.left(gone, start);
+
+    let mut now = start;
+    let mut skipped = false;
+    while !skipped {
+        let Some(event) = game.poll_events() else {
+            // only ever wait on the player who's still here
+            if let Some(deadline) = game.poll_wait_deadline() {
+                now = now.max(deadline);
+            }
+            game.advance(now);
+            continue;
+        };
+
+        absent.skip(&mut game, &event, &mut HashSet::new(), now);
+        if let (Event::WaitForDecision, State::WaitingForDecision { turn, .. }) =
+            (&event, game.current_state())
+        {
+            assert_ne!(
+                game.turn_id(*turn),
+                gone,
+                "the game is waiting on someone who left"
+            );
+        }
+        skipped = matches!(event, Event::PlayDecision(turn, _) if game.turn_id(turn) == gone);
+    }
+
+    assert!(absent.expired(start + Duration::from_secs(59)).is_empty());
+    assert_eq!(absent.expired(start + Duration::from_secs(60)), [gone]);
+    assert!(!absent.returned(gone));
+}
+
+#[test]
+fn absent_players_are_confirmed_once() {
+    let (here, gone) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut game = Game::default();
+    game.join(here);
+    game.join(gone);
+
+    let start = Instant::now();
+    let mut absent = Absent::new(Duration::from_secs(60), StrategyKind::default());
+    absent.left(gone, start);
+    let mut confirmed = HashSet::new();
+
+    // call it straight away, and play the round out
+    let mut now = start;
+    let asked = loop {
+        let Some(event) = game.poll_events() else {
+            if let Some(deadline) = game.poll_wait_deadline() {
+                now = now.max(deadline);
+            }
+            game.advance(now);
+            continue;
+        };
+
+        if let Event::WaitForNewRound { confirmations: 0 } = event {
+            break event;
+        }
+        if let Event::WaitForDecision = event {
+            game.cambio_call(here, now);
+        }
+        absent.skip(&mut game, &event, &mut confirmed, now);
+    };
+
+    absent.skip(&mut game, &asked, &mut confirmed, now);
+    assert!(confirmed.contains(&gone));
+
+    // asked again, they've already been counted
+    absent.skip(&mut game, &asked, &mut confirmed, now);
+    assert!(matches!(
+        game.current_state(),
+        State::WaitingForNewRound {
+            confirmations: 1,
+            ..
+        }
+    ));
+}
@@ -0,0 +1,117 @@
+//! Computer players, sitting in the seats people haven't taken.
+//!
+//! Bots play on the engine directly, seeing the table through [`Game::view`].
+
+use std::time::Instant;
+
+use client::{Strategy, StrategyKind};
+use common::decisions::{valid_set, Decision, Slot};
+use game::{Event, Game, State};
+use uuid::Uuid;
+
+#[derive(Default)]
+pub struct Bots {
+    /// In the order they joined, so games with the same seed play out the same.
+    strategies: Vec<(Uuid, Box<dyn Strategy>)>,
+}
+
+impl Bots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a bot playing `kind`, `seed` makes its random choices repeatable.
+    ///
+    /// Returns the id it plays under, which still has to join the game.
+    pub fn add(&mut self, kind: StrategyKind, seed: u64) -> Uuid {
+        let id = Uuid::new_v4();
+        self.strategies.push((id, kind.build(seed)));
+        id
+    }
+
+    /// Everyone the bots play as, in the order they should join.
+    pub fn ids(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.strategies.iter().map(|&(id, _)| id)
+    }
+
+    /// Have a bot playing `kind` take over the seat of player `id`.
+    pub fn take_over(&mut self, id: Uuid, kind: StrategyKind, seed: u64) {
+        self.strategies.push((id, kind.build(seed)));
+    }
+
+    fn get_mut(&mut self, id: Uuid) -> Option<&mut Box<dyn Strategy>> {
+        self.strategies
+            .iter_mut()
+            .find_map(|(bot, strategy)| (*bot == id).then_some(strategy))
+    }
+
+    /// Let the bots respond to an `event` the game just emitted.
+    pub fn act(&mut self, game: &mut Game, event: &Event, now: Instant) {
+        if self.strategies.is_empty() {
+            return;
+        }
+
+        match *event {
+            Event::StartRound(_) => {
+                for (_, strategy) in &mut self.strategies {
+                    strategy.on_new_round();
+                }
+            }
+            Event::WaitForDecision => {
+                let State::WaitingForDecision { turn, card, .. } = *game.current_state() else {
+                    return;
+                };
+                let id = game.turn_id(turn);
+                let Some(strategy) = self.get_mut(id) else {
+                    return;
+                };
+
+                let view = game.view(id);
+                if strategy.should_call_cambio(&view, game.rules()) && game.cambio_call(id, now) {
+                    return;
+                }
+
+                let valid = valid_set(card, game.rules()).into_vec();
+                let decision = strategy.choose_decision(&view, game.rules(), card, &valid);
+                if game.handle_decision(id, decision, now).is_err() {
+                    // a bad strategy shouldn't hold up the table
+                    let _ = game.handle_decision(id, Decision::Discard, now);
+                }
+            }
+            Event::WaitForSwap => {
+                let State::WaitingForSwap {
+                    turn, own, other, ..
+                } = *game.current_state()
+                else {
+                    return;
+                };
+                let id = game.turn_id(turn);
+                let Some(strategy) = self.get_mut(id) else {
+                    return;
+                };
+
+                let own = Slot {
+                    player: id,
+                    index: own,
+                };
+                let swap = strategy.choose_swap(&game.view(id), game.rules(), own, other);
+                let _ = game.handle_swap_choice(id, swap, now);
+            }
+            Event::WaitForSnap => {
+                for (id, strategy) in &mut self.strategies {
+                    if let Some(index) = strategy.should_snap(&game.view(*id)) {
+                        let slot = Slot { player: *id, index };
+                        game.handle_snap(*id, slot, None, now);
+                    }
+                }
+            }
+            Event::WaitForNewRound { confirmations: 0 } => {
+                let needed = game.seats().len();
+                for _ in 0..self.strategies.len() {
+                    game.confirm_new_round(needed, now);
+                }
+            }
+            _ => (),
+        }
+    }
+}
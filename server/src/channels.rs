@@ -13,7 +13,6 @@ pub type ClientEvents = (uuid::Uuid, client::Event);
 #[derive(Clone)]
 pub enum Connection {
     Disconnect(uuid::Uuid),
-    #[allow(dead_code)]
     Connect(uuid::Uuid),
 }
 
@@ -25,16 +24,19 @@ struct Process {
 enum ProcessKind {
     Insert {
         id: uuid::Uuid,
+        connection: uuid::Uuid,
         tx: mpsc::Sender<server::Event>,
     },
     Remove {
         id: uuid::Uuid,
+        connection: uuid::Uuid,
+        removed: oneshot::Sender<bool>,
     },
     Send(SendTo),
 }
 
 enum SendTo {
-    All(Box<dyn Fn(uuid::Uuid) -> server::Event + Send>),
+    All(Box<dyn Fn(uuid::Uuid) -> Option<server::Event> + Send>),
     One(server::Event, uuid::Uuid),
 }
 
@@ -63,16 +65,19 @@ impl Channels {
     }
 
     /// Register a new player channel, returning the sender for Client events.
+    ///
+    /// Replaces any channel from an earlier `connection` the player rejoined from.
     pub async fn register(
         &self,
         id: uuid::Uuid,
+        connection: uuid::Uuid,
         tx: mpsc::Sender<server::Event>,
     ) -> broadcast::Sender<ClientEvents> {
         let (sync, finished) = oneshot::channel();
         let _ = self
             .out
             .send(Process {
-                kind: ProcessKind::Insert { id, tx },
+                kind: ProcessKind::Insert { id, connection, tx },
                 sync,
             })
             .await;
@@ -82,16 +87,25 @@ impl Channels {
 }
 
 impl Channels {
-    pub async fn remove(&self, id: uuid::Uuid) {
+    /// Remove the player's channel, if it's still the one from `connection`.
+    ///
+    /// Returns `false` if they've since rejoined from another connection.
+    pub async fn remove(&self, id: uuid::Uuid, connection: uuid::Uuid) -> bool {
         let (sync, finished) = oneshot::channel();
+        let (removed, was_removed) = oneshot::channel();
         let _ = self
             .out
             .send(Process {
-                kind: ProcessKind::Remove { id },
+                kind: ProcessKind::Remove {
+                    id,
+                    connection,
+                    removed,
+                },
                 sync,
             })
             .await;
         finished.await.expect("failed to sync");
+        was_removed.await.unwrap_or(false)
     }
 
     pub async fn send(&self, event: server::Event, id: uuid::Uuid) {
@@ -111,16 +125,17 @@ impl Channels {
         let _ = self
             .out
             .send(Process {
-                kind: ProcessKind::Send(SendTo::All(Box::new(move |_| event.clone()))),
+                kind: ProcessKind::Send(SendTo::All(Box::new(move |_| Some(event.clone())))),
                 sync,
             })
             .await;
         finished.await.expect("failed to sync");
     }
 
+    /// Send each player their own event, skipping anyone `f` has none for.
     pub async fn broadcast_map<F>(&self, f: F)
     where
-        F: Fn(uuid::Uuid) -> server::Event + Send + 'static,
+        F: Fn(uuid::Uuid) -> Option<server::Event> + Send + 'static,
     {
         let (sync, finished) = oneshot::channel();
         let _ = self
@@ -166,42 +181,51 @@ async fn send_aggregator(mut out_rx: mpsc::Receiver<Process>) {
                     let _ = sync.send(());
                 });
             }
-            ProcessKind::Insert { id, tx } => {
-                map.write().insert(id, tx);
+            ProcessKind::Insert { id, connection, tx } => {
+                map.write().insert(id, (connection, tx));
                 let _ = sync.send(());
             }
-            ProcessKind::Remove { id } => {
-                map.write().remove(&id);
+            ProcessKind::Remove {
+                id,
+                connection,
+                removed,
+            } => {
+                let mut map = map.write();
+                let current = map.get(&id).is_some_and(|(c, _)| *c == connection);
+                if current {
+                    map.remove(&id);
+                }
+                let _ = removed.send(current);
                 let _ = sync.send(());
             }
         }
     }
 }
 
-async fn handle_send(
-    map: Arc<RwLock<HashMap<uuid::Uuid, mpsc::Sender<server::Event>>>>,
-    send: SendTo,
-) {
+/// Each player's channel, and the connection it's for.
+type Senders = HashMap<uuid::Uuid, (uuid::Uuid, mpsc::Sender<server::Event>)>;
+
+async fn handle_send(map: Arc<RwLock<Senders>>, send: SendTo) {
     match send {
         SendTo::All(cmd) => {
             let join = map
                 .read()
                 .iter()
-                .filter(|(_, sender)| !sender.is_closed())
-                .map(|(id, sender)| {
+                .filter(|(_, (_, sender))| !sender.is_closed())
+                .filter_map(|(id, (_, sender))| {
                     let sender = sender.clone();
                     let id = *id;
-                    let cmd = cmd(id);
-                    async move {
+                    let cmd = cmd(id)?;
+                    Some(async move {
                         trace!("sending cmd: `{cmd:?}` to {id}");
                         let _ = sender.send(cmd).await;
-                    }
+                    })
                 })
                 .collect::<JoinAll<_>>();
             join.await;
         }
         SendTo::One(cmd, id) => {
-            let sender = map.read().get(&id).cloned();
+            let sender = map.read().get(&id).map(|(_, sender)| sender.clone());
             if let Some(sender) = sender {
                 trace!("sending cmd: `{cmd:?}` to {id}");
                 let _ = sender.send(cmd).await;
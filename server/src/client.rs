@@ -1,21 +1,30 @@
 use std::{
+    collections::HashMap,
+    future, io,
     net::SocketAddr,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use common::{
     data::PlayerData,
-    event::{client, server},
+    event::{self, client, server, Capability, Heartbeat},
+    stream::Format,
 };
 use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
 use tokio::{
+    io::{AsyncRead, AsyncWrite},
     net::{TcpListener, TcpStream},
     sync::mpsc,
+    time,
 };
+use tokio_rustls::TlsAcceptor;
 use tracing::{error, info, trace, warn};
+use uuid::Uuid;
 
 use crate::{
     channels::Connection,
@@ -24,9 +33,13 @@ use crate::{
     Channels, GameData,
 };
 
+/// Accept connections, from anyone while `enabled`, otherwise only players rejoining.
+///
+/// Every connection has to be over TLS if there's a `tls` acceptor.
 pub async fn connect(
     config: Config,
-    mut data: GameData,
+    tls: Option<TlsAcceptor>,
+    data: GameData,
     channels: Channels,
     disconnects: Disconnects,
 ) -> (Arc<AtomicBool>, tokio::task::AbortHandle) {
@@ -36,19 +49,79 @@ pub async fn connect(
 
     info!("listening on {:?}", listener.local_addr().ok());
 
+    let websockets = match config.websocket_port {
+        Some(port) => {
+            let listener = TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], port)))
+                .await
+                .expect("failed to create websocket port");
+            info!(
+                "listening for websockets on {:?}",
+                listener.local_addr().ok()
+            );
+            Some(listener)
+        }
+        None => None,
+    };
+
     let enabled = Arc::new(AtomicBool::new(false));
 
+    let offer = Offer {
+        formats: if config.json_only {
+            &[Format::Json]
+        } else {
+            Format::ALL
+        },
+        heartbeat: config.heartbeat(),
+    };
+
     let task = tokio::spawn({
         let enabled = Arc::clone(&enabled);
         async move {
-            while let Ok((stream, addr)) = listener.accept().await {
-                let accepting = enabled.load(Ordering::Relaxed);
-                if accepting {
-                    try_connect(stream, addr, &mut data, &channels, disconnects.clone()).await;
-                } else {
-                    trace!("not accepting, dropped {addr}");
-                    drop(stream);
-                }
+            // each player's session token, to check it's really them rejoining
+            let tokens = Tokens::default();
+
+            loop {
+                let (stream, addr, websocket) = tokio::select! {
+                    Ok((stream, addr)) = listener.accept() => (stream, addr, false),
+                    Ok((stream, addr)) = accept(websockets.as_ref()) => (stream, addr, true),
+                    else => break,
+                };
+
+                info!(websocket, "connection from {addr}");
+
+                // handshake on the side, so a slow client can't hold up everyone else
+                let tls = tls.clone();
+                let enabled = Arc::clone(&enabled);
+                let tokens = Arc::clone(&tokens);
+                let data = Arc::clone(&data);
+                let channels = Arc::clone(&channels);
+                let disconnects = disconnects.clone();
+                tokio::spawn(async move {
+                    let handshake = async {
+                        let connection = match open(stream, tls.as_ref(), websocket).await {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                warn!("failed to open connection from {addr}: {e}");
+                                return;
+                            }
+                        };
+
+                        try_connect(
+                            connection,
+                            offer,
+                            &enabled,
+                            &tokens,
+                            &data,
+                            &channels,
+                            disconnects,
+                        )
+                        .await;
+                    };
+
+                    if time::timeout(HANDSHAKE_TIMEOUT, handshake).await.is_err() {
+                        warn!("connection from {addr} timed out before joining");
+                    }
+                });
             }
         }
     });
@@ -56,16 +129,54 @@ pub async fn connect(
     (enabled, task.abort_handle())
 }
 
+/// Finish opening a connection, securing it first if there's a `tls` acceptor.
+async fn open(
+    stream: TcpStream,
+    tls: Option<&TlsAcceptor>,
+    websocket: bool,
+) -> io::Result<PlayerConn> {
+    async fn over<S>(stream: S, websocket: bool) -> io::Result<PlayerConn>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        if websocket {
+            PlayerConn::websocket(stream)
+                .await
+                .map_err(io::Error::other)
+        } else {
+            Ok(PlayerConn::from(stream))
+        }
+    }
+
+    match tls {
+        Some(tls) => over(tls.accept(stream).await?, websocket).await,
+        None => over(stream, websocket).await,
+    }
+}
+
+/// The next connection to `listener`, if there is one to listen on.
+async fn accept(listener: Option<&TcpListener>) -> io::Result<(TcpStream, SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => future::pending().await,
+    }
+}
+
 #[derive(Clone)]
-pub struct Disconnects(mpsc::Sender<uuid::Uuid>);
+/// Players whose connection closed, with the connection it was.
+pub struct Disconnects(mpsc::Sender<(Uuid, Uuid)>);
 
 pub fn disconnect(channels: Channels) -> (Disconnects, tokio::task::AbortHandle) {
     let (tx, mut rx) = mpsc::channel(16);
 
     let task = tokio::spawn(async move {
-        while let Some(id) = rx.recv().await {
+        while let Some((id, connection)) = rx.recv().await {
+            if !channels.remove(id, connection).await {
+                // they've already rejoined, it's only the old connection that's gone
+                info!("client {id} left an old connection");
+                continue;
+            }
             info!("client {id} has left");
-            channels.remove(id).await;
             channels.broadcast_event(server::Event::Left { id }).await;
             // let subscribers know theres been a disconnection
             let _ = channels.connections().send(Connection::Disconnect(id));
@@ -75,19 +186,36 @@ pub fn disconnect(channels: Channels) -> (Disconnects, tokio::task::AbortHandle)
     (Disconnects(tx), task.abort_handle())
 }
 
+/// Each player's session token, by their id.
+type Tokens = Arc<Mutex<HashMap<Uuid, Uuid>>>;
+
+/// How long a connection gets to say hello and join, before it's dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the server offers every client that says hello.
+#[derive(Clone, Copy)]
+struct Offer {
+    /// The wire formats we speak.
+    formats: &'static [Format],
+    heartbeat: Option<Heartbeat>,
+}
+
 async fn try_connect(
-    stream: TcpStream,
-    addr: SocketAddr,
-    data: &mut GameData,
+    mut connection: PlayerConn,
+    offer: Offer,
+    enabled: &AtomicBool,
+    tokens: &Tokens,
+    data: &GameData,
     channels: &Channels,
     disconnects: Disconnects,
 ) {
-    info!("connection from {addr}");
-
-    let mut connection = PlayerConn::from(stream);
+    // make sure we speak the same protocol
+    if !hello_handshake(offer, &mut connection).await {
+        return;
+    }
 
     // retrieve id of player
-    let Some(id) = id_handshake(data, &mut connection).await else {
+    let Some(id) = id_handshake(data, tokens, enabled, &mut connection).await else {
         return;
     };
 
@@ -95,33 +223,113 @@ async fn try_connect(
     trace!("sending join");
     channels.broadcast_event(server::Event::Joined { id }).await;
 
-    // let subscribers know theres a new connection
-    let _ = channels.connections().send(Connection::Connect(id));
-
     // spawn a player task
-    let left = player::spawn(Arc::clone(data), channels, id, connection).await;
+    // so it's only this connection closing that counts as them leaving
+    let tag = Uuid::new_v4();
+    let left = player::spawn(Arc::clone(data), channels, id, tag, connection).await;
 
     // let the disconnect handler know
     tokio::spawn(async move {
         if left.await.is_ok() {
-            let _ = disconnects.0.send(id).await;
+            let _ = disconnects.0.send((id, tag)).await;
         }
     });
 
     // signal to the player that they can join the event loop
     channels.send(server::Event::Enter, id).await;
+
+    // let subscribers know theres a new connection, now it can be sent to
+    let _ = channels.connections().send(Connection::Connect(id));
 }
 
-async fn id_handshake(data: &mut GameData, connection: &mut PlayerConn) -> Option<uuid::Uuid> {
+/// Exchange versions with the client, and pick the first of their wire formats we speak.
+///
+/// Returns `false` if it can't play here.
+async fn hello_handshake(offer: Offer, connection: &mut PlayerConn) -> bool {
+    let Some(Ok(client::Event::Hello {
+        protocol_version,
+        client_name,
+        capabilities,
+        formats: offered,
+    })) = connection.read.next().await
+    else {
+        warn!("connection refused as client never said hello");
+        return false;
+    };
+
+    if !event::is_compatible(protocol_version) {
+        warn!("connection refused as {client_name} speaks protocol version {protocol_version}");
+        let _ = connection
+            .write
+            .send(server::Event::IncompatibleVersion {
+                client: protocol_version,
+                min_supported: event::MIN_PROTOCOL_VERSION,
+                max_supported: event::PROTOCOL_VERSION,
+            })
+            .await;
+        return false;
+    }
+
+    let format = offered
+        .into_iter()
+        .find(|format| offer.formats.contains(format))
+        .unwrap_or_default();
+
+    // older clients wouldn't know what to do with a ping
+    let heartbeat = offer
+        .heartbeat
+        .filter(|_| capabilities.contains(&Capability::Heartbeat));
+
+    info!(
+        ?capabilities,
+        "{client_name} speaks protocol version {protocol_version} in {format:?}"
+    );
+
+    // only what they offered, older clients can't decode what they don't know
+    let capabilities = Capability::ALL
+        .iter()
+        .copied()
+        .filter(|capability| capabilities.contains(capability))
+        .collect();
+    let hello = server::Event::Hello {
+        protocol_version: event::PROTOCOL_VERSION,
+        capabilities,
+        format,
+        heartbeat,
+    };
+    if let Err(e) = connection.write.send(hello).await {
+        error!("failed to greet client: {e}");
+        return false;
+    }
+
+    connection.read.set_format(format);
+    connection.write.set_format(format);
+    connection.heartbeat = heartbeat;
+
+    true
+}
+
+async fn id_handshake(
+    data: &GameData,
+    tokens: &Tokens,
+    enabled: &AtomicBool,
+    connection: &mut PlayerConn,
+) -> Option<Uuid> {
     let Some(Ok(client::Event::Join(join))) = connection.read.next().await else {
         warn!("connection refused as client never requested to join");
         return None;
     };
 
-    let id = retrieve_or_create_id(data, join);
+    // only checked now, the game could have started while they said hello
+    let open = enabled.load(Ordering::Relaxed);
+    let (id, token) = retrieve_or_create_id(data, &mut tokens.lock(), open, join)?;
 
     // assign the player their id
-    if let Err(e) = connection.write.send(server::Event::AssignId { id }).await {
+    if let Err(e) = connection
+        .write
+        .send(server::Event::AssignId { id, token })
+        .await
+    {
         error!("failed to assign id to client: {e}");
         return None;
     }
@@ -129,25 +337,127 @@ async fn id_handshake(data: &mut GameData, connection: &mut PlayerConn) -> Optio
     Some(id)
 }
 
-fn retrieve_or_create_id(data: &mut GameData, join: client::Join) -> uuid::Uuid {
-    let create_new = || {
+/// The id and session token of the player joining,
+/// `None` if they aren't allowed in.
+fn retrieve_or_create_id(
+    data: &GameData,
+    tokens: &mut HashMap<Uuid, Uuid>,
+    open: bool,
+    join: client::Join,
+) -> Option<(Uuid, Uuid)> {
+    let mut create_new = || {
+        if !open {
+            warn!("connection refused as the game has already started");
+            return None;
+        }
+
         let player = PlayerData::new();
         let id = player.id();
 
         data.lock().try_add_player(player);
 
-        id
+        let token = Uuid::new_v4();
+        tokens.insert(id, token);
+
+        Some((id, token))
     };
 
     match join {
         client::Join::New => create_new(),
-        client::Join::Existing(id) if !data.lock().exists(id) => {
+        client::Join::Existing { id, .. } if !data.lock().exists(id) => {
             warn!("client tried to connect with invalid id");
             create_new()
         }
-        client::Join::Existing(id) => {
-            debug_assert!(data.lock().exists(id));
-            id
+        client::Join::Existing { id, token } if tokens.get(&id) != Some(&token) => {
+            warn!("connection refused as client gave the wrong token for {id}");
+            None
         }
+        client::Join::Existing { id, token } => Some((id, token)),
+    }
+}
+
+#[tokio::test]
+async fn older_clients_understand_hello() {
+    use serde::Deserialize;
+
+    // how a version 2 client sees the server's hello
+    #[derive(Deserialize)]
+    enum OldCapability {
+        Rejoin,
+        Timers,
+    }
+    #[derive(Deserialize)]
+    enum OldEvent {
+        Hello {
+            protocol_version: u32,
+            capabilities: Vec<OldCapability>,
+        },
     }
+
+    let (client, server) = tokio::io::duplex(1024);
+    let (mut read, mut write) = common::stream::split::<OldEvent, client::Event, _>(client);
+    let offer = Offer {
+        formats: Format::ALL,
+        heartbeat: Some(Heartbeat {
+            interval: Duration::from_secs(5),
+            misses: 3,
+        }),
+    };
+    let greet = tokio::spawn(async move {
+        let mut connection = PlayerConn::from(server);
+        hello_handshake(offer, &mut connection).await
+    });
+
+    let hello = client::Event::Hello {
+        protocol_version: 2,
+        client_name: "old".into(),
+        capabilities: vec![Capability::Rejoin, Capability::Timers],
+        formats: vec![],
+    };
+    write.send(hello).await.unwrap();
+    assert!(greet.await.unwrap());
+
+    let OldEvent::Hello {
+        protocol_version,
+        capabilities,
+    } = read.next().await.unwrap().unwrap();
+    assert_eq!(protocol_version, event::PROTOCOL_VERSION);
+    assert_eq!(capabilities.len(), 2);
+}
+
+#[tokio::test]
+async fn old_connections_closing_dont_count_as_leaving() {
+    use crate::channels;
+
+    let channels = Arc::new(channels::Channels::start());
+    let (disconnects, _) = disconnect(Arc::clone(&channels));
+    let mut connections = channels.connections().subscribe();
+
+    // they rejoined before the server noticed their old connection had died
+    let (id, old, new) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let (old_tx, _old_rx) = mpsc::channel(8);
+    let (new_tx, mut new_rx) = mpsc::channel(8);
+    channels.register(id, old, old_tx).await;
+    channels.register(id, new, new_tx).await;
+
+    // someone else really leaves, after the old connection closes
+    let (other, other_connection) = (Uuid::new_v4(), Uuid::new_v4());
+    let (other_tx, _other_rx) = mpsc::channel(8);
+    channels.register(other, other_connection, other_tx).await;
+    disconnects.0.send((id, old)).await.unwrap();
+    disconnects.0.send((other, other_connection)).await.unwrap();
+
+    let Ok(Connection::Disconnect(left)) = connections.recv().await else {
+        panic!("someone should have left");
+    };
+    assert_eq!(left, other);
+    assert!(connections.try_recv().is_err());
+
+    // and they're still hearing from the game
+    assert!(matches!(
+        new_rx.recv().await,
+        Some(server::Event::Left { id }) if id == other
+    ));
+    channels.send(server::Event::Ping, id).await;
+    assert!(matches!(new_rx.recv().await, Some(server::Event::Ping)));
 }
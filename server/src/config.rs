@@ -1,5 +1,8 @@
-use std::io::Read;
+use std::{io::Read, path::PathBuf, time::Duration};
 
+use anyhow::Context as _;
+use client::StrategyKind;
+use common::{event::Heartbeat, rules::Preset};
 use serde::{Deserialize, Serialize};
 
 /// Minimum number of players required to play cambio
@@ -8,7 +11,10 @@ pub const MIN_PLAYER_COUNT: usize = 2;
 pub const MAX_PLAYER_COUNT: usize = 8;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    #[serde(default = "defaults::decision_time")]
+    pub decision_time_secs: u64,
     #[serde(default = "defaults::snap_time")]
     pub snap_time_secs: u64,
     #[serde(default = "defaults::new_round")]
@@ -17,9 +23,54 @@ pub struct Config {
     pub show_all_cooldown: u64,
     #[serde(default = "defaults::port")]
     pub server_port: u16,
+    /// Also accept WebSocket connections on this port, for browsers.
+    #[serde(default)]
+    pub websocket_port: Option<u16>,
+    /// How long a player who drops out mid-game has to rejoin,
+    /// before a bot takes their seat.
+    #[serde(default = "defaults::reconnect_grace")]
+    pub reconnect_grace_secs: u64,
+    /// How often to ping clients, 0 to never ping them.
+    #[serde(default = "defaults::heartbeat_interval")]
+    pub heartbeat_interval_secs: u64,
+    /// Pings a client can miss before they're disconnected.
+    #[serde(default = "defaults::heartbeat_misses")]
+    pub heartbeat_misses: u32,
+    /// House rules the lobby starts with, the host can pick others.
+    #[serde(default)]
+    pub rules: Preset,
+    /// Append a log of every game to this file, one JSON entry per line.
+    #[serde(default)]
+    pub game_log: Option<PathBuf>,
+    /// Fill empty seats with bots until there are this many players.
+    #[serde(default)]
+    pub fill_seats_to: usize,
+    /// How the bots play.
+    #[serde(default)]
+    pub bot_strategy: StrategyKind,
+    /// Talk to clients in JSON, rather than a binary format, to read what's sent.
+    #[serde(default)]
+    pub json_only: bool,
+    /// Secure every connection, over TCP and WebSockets, with TLS.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// Where to find the server's certificate, both in PEM files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// The certificate chain, the server's own certificate first.
+    pub cert: PathBuf,
+    /// The private key for the certificate.
+    pub key: PathBuf,
 }
 
 pub mod defaults {
+    pub const fn decision_time() -> u64 {
+        10
+    }
+
     pub const fn snap_time() -> u64 {
         5
     }
@@ -35,30 +86,107 @@ pub mod defaults {
     pub const fn port() -> u16 {
         25580
     }
+
+    pub const fn reconnect_grace() -> u64 {
+        60
+    }
+
+    pub const fn heartbeat_interval() -> u64 {
+        5
+    }
+
+    pub const fn heartbeat_misses() -> u32 {
+        3
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            decision_time_secs: defaults::decision_time(),
             snap_time_secs: defaults::snap_time(),
             new_round_timer_secs: defaults::new_round(),
             show_all_cooldown: defaults::show_all_cooldown(),
             server_port: defaults::port(),
+            websocket_port: None,
+            reconnect_grace_secs: defaults::reconnect_grace(),
+            heartbeat_interval_secs: defaults::heartbeat_interval(),
+            heartbeat_misses: defaults::heartbeat_misses(),
+            rules: Preset::default(),
+            game_log: None,
+            fill_seats_to: 0,
+            bot_strategy: StrategyKind::default(),
+            json_only: false,
+            tls: None,
         }
     }
 }
 
+impl Config {
+    /// How long the game waits for players.
+    pub fn timings(&self) -> game::Timings {
+        game::Timings {
+            decision: Duration::from_secs(self.decision_time_secs),
+            snap: Duration::from_secs(self.snap_time_secs),
+            new_round: Duration::from_secs(self.new_round_timer_secs),
+            show_all: Duration::from_secs(self.show_all_cooldown),
+        }
+    }
+
+    /// How clients are checked on, `None` if they aren't.
+    pub fn heartbeat(&self) -> Option<Heartbeat> {
+        (self.heartbeat_interval_secs > 0 && self.heartbeat_misses > 0).then(|| Heartbeat {
+            interval: Duration::from_secs(self.heartbeat_interval_secs),
+            misses: self.heartbeat_misses,
+        })
+    }
+}
+
 const DEFAULT_CONFIG_PATH: &str = "./Server.toml";
 
-pub fn load() -> anyhow::Result<Config> {
-    let mut file = std::fs::File::options()
+/// Load the config file.
+///
+/// Returns `None` if there is no config file.
+pub fn load() -> anyhow::Result<Option<Config>> {
+    let mut file = match std::fs::File::options()
         .read(true)
-        .open(DEFAULT_CONFIG_PATH)?;
+        .open(DEFAULT_CONFIG_PATH)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context(format!("failed to open {DEFAULT_CONFIG_PATH}")),
+    };
 
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
 
-    let config = toml::from_str(&contents)?;
+    let config = toml::from_str(&contents)
+        .with_context(|| format!("invalid config in {DEFAULT_CONFIG_PATH}"))?;
+
+    Ok(Some(config))
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let config = toml::from_str::<Config>("snap_time_secs = 3\nserver_port = 1234");
+    assert!(config.is_ok());
+
+    // the config we ship has to load
+    let config = toml::from_str::<Config>(include_str!("../../Server.toml"));
+    assert!(config.is_ok());
+
+    let err = toml::from_str::<Config>("port = 1234").unwrap_err();
+    assert!(err.message().contains("unknown field `port`"));
+}
+
+#[test]
+fn bot_strategy_is_parsed() {
+    let config = toml::from_str::<Config>("fill_seats_to = 4\nbot_strategy = \"cautious\"");
+    assert_eq!(config.unwrap().bot_strategy, StrategyKind::Cautious);
+}
 
-    Ok(config)
+#[test]
+fn tls_section_is_parsed() {
+    let config = toml::from_str::<Config>("[tls]\ncert = \"cert.pem\"\nkey = \"key.pem\"");
+    assert_eq!(config.unwrap().tls.unwrap().key, PathBuf::from("key.pem"));
 }
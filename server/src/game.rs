@@ -1,46 +1,106 @@
 use std::{
     collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write as _},
+    path::Path,
     time::Instant,
 };
 
 use common::{
-    event::server::{self, Winner},
+    data::{PlayerView, Resync, Standing},
+    decisions::{Decision, Slot},
+    event::server,
     Card,
 };
 pub use game::Game;
-use itertools::Itertools as _;
-use tracing::debug;
+use game::State;
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::{Channels, GameData};
+use crate::{absent::Absent, bots::Bots, channels::Connection, Channels, GameData};
+
+/// Open the file games are logged to, appending to what's already there.
+pub fn open_log(path: &Path) -> Option<BufWriter<File>> {
+    match File::options().create(true).append(true).open(path) {
+        Ok(file) => Some(BufWriter::new(file)),
+        Err(e) => {
+            warn!(path = %path.display(), "failed to open game log: {e}");
+            None
+        }
+    }
+}
+
+pub async fn run(
+    game: &mut Game,
+    bots: &mut Bots,
+    absent: &mut Absent,
+    mut log: Option<BufWriter<File>>,
+    data: &GameData,
+    channels: &Channels,
+) {
+    if log.is_some() {
+        game.start_log(Instant::now());
+    }
+
+    // everyone in the lobby takes a seat at the table
+    for player in data.lock().players() {
+        game.join(player.id());
+    }
+    for id in bots.ids() {
+        game.join(id);
+    }
+
+    channels
+        .broadcast_event(server::Event::Rules(game.rules().clone()))
+        .await;
+    channels
+        .broadcast_event(server::Event::Timers {
+            decision: game.timings().decision,
+            snap: game.timings().snap,
+        })
+        .await;
 
-pub async fn run(game: &mut Game, data: &GameData, channels: &Channels) {
     let mut incoming = channels.incoming();
-    let mut confirmed = HashSet::with_capacity(data.lock().player_count());
+    let mut connections = channels.connections().subscribe();
+    let mut confirmed = HashSet::with_capacity(game.seats().len());
 
     'game_loop: loop {
+        write_log(game, &mut log);
+
         // make sure we process all events first
         if let Some(game_event) = game.poll_events() {
+            let seen = game_event.clone();
+
             match to_server_event_simple_broadcast(game_event) {
                 Ok(event) => channels.broadcast_event(event).await,
                 Err(complex_event) => match complex_event {
-                    game::Event::Setup => {
-                        setup(game, data);
-                    }
-                    game::Event::FirstPeek => first_peek(data, channels).await,
-                    game::Event::StartTurn(turn) => start_turn(turn, data, channels).await,
+                    game::Event::Setup(seed) => setup(seed, channels).await,
+                    game::Event::FirstPeek => first_peek(game, channels).await,
+                    game::Event::StartTurn(turn) => start_turn(turn, game, channels).await,
                     game::Event::DrawCard(turn, card) => {
-                        draw_card(card, turn, data, channels).await
+                        draw_card(card, turn, game, channels).await
+                    }
+                    game::Event::PlayDecision(turn, decision) => {
+                        play_decision(decision, turn, game, channels).await
+                    }
+                    game::Event::Peek(turn, slot, card) => {
+                        peek(slot, card, turn, game, channels).await
                     }
                     game::Event::WaitForNewRound { confirmations } => {
                         ask_to_confirm(confirmations, &mut confirmed, channels).await
                     }
-                    game::Event::FindWinner => find_winner(data, channels).await,
+                    game::Event::ShowAll => show_all(game, channels).await,
+                    game::Event::MatchResult(winner, standings) => {
+                        match_result(winner, standings, game, channels).await
+                    }
                     game::Event::Exit => break 'game_loop,
                     event => panic!("Unhandled game event: {event:?}"),
                 },
             }
 
+            bots.act(game, &seen, Instant::now());
+            absent.skip(game, &seen, &mut confirmed, Instant::now());
+
             continue;
         }
 
@@ -48,21 +108,52 @@ pub async fn run(game: &mut Game, data: &GameData, channels: &Channels) {
 
         if let Some(deadline) = game.poll_wait_deadline() {
             // wait to recieve something inside of the deadline
-            if let Ok(Ok((id, event))) =
-                tokio::time::timeout_at(deadline.into(), incoming.recv()).await
-            {
-                handle_incoming_event(game, data, event, id, &mut confirmed).await;
+            tokio::select! {
+                received = tokio::time::timeout_at(deadline.into(), incoming.recv()) => {
+                    if let Ok(Ok((id, event))) = received {
+                        handle_incoming_event(game, channels, event, id, &mut confirmed).await;
+                    }
+                }
+                Ok(connection) = connections.recv() => {
+                    handle_connection(game, absent, channels, connection).await;
+                }
             }
         }
 
+        // seats nobody came back for are given to bots
+        for id in absent.expired(Instant::now()) {
+            info!(%id, "player didn't rejoin in time, a bot is taking over");
+            bots.take_over(id, absent.stand_in(), rand::random());
+            data.lock().remove_player(id);
+        }
+
         // advance the game state
-        game.advance();
+        game.advance(Instant::now());
     }
 
+    write_log(game, &mut log);
+
     // always make sure we tell the clients the game has ended
     channels.broadcast_event(server::Event::GameEnd).await;
 }
 
+/// Append everything the game has recorded since last time.
+fn write_log(game: &mut Game, log: &mut Option<BufWriter<File>>) {
+    let Some(writer) = log else {
+        return;
+    };
+
+    let written = game
+        .drain_log()
+        .try_for_each(|entry| game::replay::append(writer, &entry))
+        .and_then(|()| writer.flush());
+
+    if let Err(e) = written {
+        warn!("failed to write game log, no longer logging: {e}");
+        *log = None;
+    }
+}
+
 async fn ask_to_confirm(confirmations: usize, confirmed: &mut HashSet<Uuid>, channels: &Channels) {
     // the first time this event is emitted (no confirms yet...)
     if confirmations == 0 {
@@ -75,90 +166,144 @@ async fn ask_to_confirm(confirmations: usize, confirmed: &mut HashSet<Uuid>, cha
     }
 }
 
-fn get_id_from_turn(turn: usize, data: &GameData) -> Uuid {
-    let data = data.lock();
-    let index = turn % data.player_count();
-    data.get_player(index).id()
-}
-
-fn setup(game: &mut Game, data: &GameData) {
-    let mut data = data.lock();
+async fn setup(seed: u64, channels: &Channels) {
+    // the seed reproduces the whole deck, so players are only told it once the match is over
+    info!(seed, "setting up round");
 
-    for i in 0..data.player_count() {
-        common::data::take_starting_cards(&mut game.deck, &mut data, i);
-    }
+    channels.broadcast_event(server::Event::Setup).await;
+}
 
-    debug!("set players up");
+async fn match_result(winner: Uuid, standings: Vec<Standing>, game: &Game, channels: &Channels) {
+    let seed = game.seed();
+    channels
+        .broadcast_event(server::Event::MatchResult {
+            winner,
+            standings,
+            seed,
+        })
+        .await;
 }
 
-async fn first_peek(data: &GameData, channels: &Channels) {
-    let first_cards = data
-        .lock()
-        .players()
+/// Everyone's view of the table, so nobody is sent cards they haven't seen.
+fn views(game: &Game) -> HashMap<Uuid, PlayerView> {
+    game.seats()
         .iter()
-        .map(|p| {
-            let id = p.id();
-            let [a, b] = p.cards()[..2] else {
-                unreachable!()
-            };
+        .map(|seat| (seat.id(), game.view(seat.id())))
+        .collect()
+}
 
-            (id, (a, b))
-        })
-        .collect::<HashMap<_, _>>();
+async fn first_peek(game: &Game, channels: &Channels) {
+    let views = views(game);
 
+    // anyone who joined too late to get a seat has nothing to see
     channels
-        .broadcast_map(move |id| {
-            let (a, b) = first_cards[&id];
-            server::Event::FirstPeek(a, b)
-        })
+        .broadcast_map(move |id| views.get(&id).cloned().map(server::Event::FirstPeek))
         .await;
 }
 
-async fn start_turn(turn: usize, data: &GameData, channels: &Channels) {
-    let id = get_id_from_turn(turn, data);
+async fn start_turn(turn: usize, game: &Game, channels: &Channels) {
+    let id = game.turn_id(turn);
 
     channels
         .broadcast_event(server::Event::TurnStart { id })
         .await;
 }
 
-async fn draw_card(card: Card, turn: usize, data: &GameData, channels: &Channels) {
-    let id = get_id_from_turn(turn, data);
+async fn draw_card(card: Card, turn: usize, game: &Game, channels: &Channels) {
+    let id = game.turn_id(turn);
 
     channels.send(server::Event::DrawCard(card), id).await;
 }
 
-async fn find_winner(data: &GameData, channels: &Channels) {
-    let winner_result = {
-        let data = data.lock();
-        let scores = data.players().iter().into_group_map_by(|p| p.score());
-        let winner = scores
-            .iter()
-            .min_by(|(a, _), (b, _)| a.cmp(b))
-            .and_then(|(_, players)| {
-                if let [winner] = players.as_slice() {
-                    Some(winner)
-                } else {
-                    // no sole winner
-                    None
-                }
-            });
+async fn play_decision(decision: Decision, turn: usize, game: &Game, channels: &Channels) {
+    let id = game.turn_id(turn);
 
-        if let Some(winner) = winner {
-            Winner::Player { uuid: winner.id() }
-        } else {
-            Winner::Tied
-        }
-    };
+    channels
+        .broadcast_event(server::Event::PlayAction { id, decision })
+        .await;
+}
+
+async fn peek(slot: Slot, card: Card, turn: usize, game: &Game, channels: &Channels) {
+    let id = game.turn_id(turn);
+
+    channels.send(server::Event::Peek { slot, card }, id).await;
+}
+
+async fn show_all(game: &Game, channels: &Channels) {
+    let views = views(game);
 
+    // anyone who joined too late to get a seat has nothing to see
     channels
-        .broadcast_event(server::Event::Winner(winner_result))
+        .broadcast_map(move |id| views.get(&id).cloned().map(server::Event::ShowAll))
         .await;
 }
 
+/// Keep the seat of anyone who drops out, and catch them up when they're back.
+async fn handle_connection(
+    game: &mut Game,
+    absent: &mut Absent,
+    channels: &Channels,
+    connection: Connection,
+) {
+    let now = Instant::now();
+    let seated = |id| game.seats().iter().any(|seat| seat.id() == id);
+
+    match connection {
+        Connection::Disconnect(id) if seated(id) => {
+            info!(%id, "player dropped out, keeping their seat");
+            absent.left(id, now);
+            absent.skip_turn(game, now);
+        }
+        Connection::Connect(id) if seated(id) => {
+            if absent.returned(id) {
+                info!(%id, "player rejoined");
+            }
+            resync(id, game, channels).await;
+        }
+        _ => (),
+    }
+}
+
+/// Catch a player up on the table after they rejoin.
+async fn resync(id: Uuid, game: &Game, channels: &Channels) {
+    let state = *game.current_state();
+    let turn = state.turn().map(|turn| game.turn_id(turn));
+    let resync = Resync {
+        view: game.view(id),
+        round: state.round(),
+        turn,
+    };
+
+    let mut events = vec![
+        server::Event::Rules(game.rules().clone()),
+        server::Event::Timers {
+            decision: game.timings().decision,
+            snap: game.timings().snap,
+        },
+        server::Event::Resync(resync),
+    ];
+
+    // and whatever the table is waiting on
+    match state {
+        State::WaitingForDecision { card, .. } if turn == Some(id) => {
+            events.push(server::Event::DrawCard(card));
+            events.push(server::Event::WaitingForDecision);
+        }
+        State::WaitingForDecision { .. } => events.push(server::Event::WaitingForDecision),
+        State::WaitingForSwap { .. } => events.push(server::Event::WaitingForSwapChoice),
+        State::WaitingForSnaps { .. } => events.push(server::Event::WaitingForSnap),
+        State::WaitingForNewRound { .. } => events.push(server::Event::ConfirmNewRound),
+        _ => (),
+    }
+
+    for event in events {
+        channels.send(event, id).await;
+    }
+}
+
 async fn handle_incoming_event(
     game: &mut Game,
-    data: &GameData,
+    channels: &Channels,
     event: common::event::client::Event,
     from_id: Uuid,
     confirmed: &mut HashSet<Uuid>,
@@ -167,16 +312,39 @@ async fn handle_incoming_event(
 
     debug!(event = ?event, "handling event");
 
-    match event {
-        ClientEvent::Snap => game.handle_snap(Card::Joker, Instant::now()),
-        ClientEvent::Decision(decision) => game.handle_decision(decision, Instant::now()),
-        ClientEvent::ConfirmNewRound => {
-            if confirmed.insert(from_id) {
-                game.confirm_new_round(data.lock().player_count(), Instant::now())
-            }
+    let rejected = match event {
+        ClientEvent::Decision(decision) => game
+            .handle_decision(from_id, decision, Instant::now())
+            .err(),
+        ClientEvent::ChooseSwap(swap) => {
+            game.handle_swap_choice(from_id, swap, Instant::now()).err()
         }
-        ClientEvent::SkipNewRound => game.skip_new_round(),
-        _ => (),
+        ClientEvent::Snap { slot, give } => {
+            game.handle_snap(from_id, slot, give, Instant::now());
+            None
+        }
+        ClientEvent::CallCambio => {
+            game.cambio_call(from_id, Instant::now());
+            None
+        }
+        ClientEvent::ConfirmNewRound if confirmed.insert(from_id) => {
+            game.confirm_new_round(game.seats().len(), Instant::now());
+            None
+        }
+        ClientEvent::SkipNewRound => {
+            game.skip_new_round();
+            None
+        }
+        _ => None,
+    };
+
+    // let the client know, so a buggy one can tell why nothing happened
+    if let Some(rejection) = rejected {
+        info!(id = %from_id, "rejected input: {rejection}");
+        let reason = rejection.to_string();
+        channels
+            .send(server::Event::Rejected { reason }, from_id)
+            .await;
     }
 }
 
@@ -188,15 +356,28 @@ fn to_server_event_simple_broadcast(event: game::Event) -> Result<server::Event,
         game::Event::WaitForSnap => server::Event::WaitingForSnap,
         game::Event::EndTurn(..) => server::Event::EndTurn,
         game::Event::EndRound(..) => server::Event::RoundEnd,
-        game::Event::Cambio => server::Event::CambioCall,
+        game::Event::Cambio(id) => server::Event::CambioCall { id },
+        game::Event::FindWinner(id) => server::Event::Winner { id },
+        game::Event::RoundScores(standings) => server::Event::RoundScores(standings),
+        game::Event::Discard(card) => server::Event::Discard(card),
+        game::Event::Reshuffle => server::Event::Reshuffle,
+        game::Event::WaitForSwap => server::Event::WaitingForSwapChoice,
+        game::Event::Swap(a, b) => server::Event::Swapped(a, b),
+        game::Event::Snapped(id, slot, card) => server::Event::Snapped { id, slot, card },
+        game::Event::WrongSnap(id, slot, card) => server::Event::WrongSnap { id, slot, card },
+        game::Event::Penalty(id) => server::Event::Penalty { id },
+        game::Event::GiveCard(from, to) => server::Event::GaveCard { from, to },
         // ----
         game::Event::WaitForNewRound { .. } => return Err(event),
-        game::Event::Setup => return Err(event),
+        game::Event::PlayDecision(..) => return Err(event),
+        game::Event::Peek(..) => return Err(event),
         game::Event::DrawCard(..) => return Err(event),
+        game::Event::MatchResult(..) => return Err(event),
         game::Event::Exit => return Err(event),
         game::Event::FirstPeek => return Err(event),
         game::Event::StartTurn(_) => return Err(event),
-        game::Event::FindWinner => return Err(event),
+        game::Event::Setup(..) => return Err(event),
+        game::Event::ShowAll => return Err(event),
     };
 
     Ok(event)
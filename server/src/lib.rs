@@ -1,37 +1,46 @@
+mod absent;
+pub mod bots;
 mod channels;
 mod client;
 pub mod config;
 mod game;
 mod lobby;
 mod player;
+mod tls;
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use common::event::server::{self};
 use config::Config;
 use parking_lot::Mutex;
+use tokio_rustls::TlsAcceptor;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info};
+use tracing::info;
 
 type GameData = Arc<Mutex<common::data::GameData>>;
 type Channels = Arc<channels::Channels>;
 
 pub struct GameServer {
     config: Config,
+    tls: Option<TlsAcceptor>,
 }
 
 impl GameServer {
-    pub fn from_config() -> Self {
-        let config = match config::load() {
-            Ok(cfg) => cfg,
-            Err(e) => {
-                error!("error loading config: {e}");
-                info!("using default config");
+    pub fn from_config() -> anyhow::Result<Self> {
+        let config = match config::load()? {
+            Some(cfg) => cfg,
+            None => {
+                info!("no config found, using default config");
                 Config::default()
             }
         };
 
-        GameServer { config }
+        let tls = config.tls.as_ref().map(tls::acceptor).transpose()?;
+        if tls.is_some() {
+            info!("accepting connections over TLS");
+        }
+
+        Ok(GameServer { config, tls })
     }
 
     pub async fn run(&self, token: CancellationToken) {
@@ -41,22 +50,40 @@ impl GameServer {
         let (disconnects, disconnect_handle) = client::disconnect(Arc::clone(&channels));
         let (connect_enabled, connect_handle) = client::connect(
             self.config.clone(),
+            self.tls.clone(),
             Arc::clone(&data),
             Arc::clone(&channels),
             disconnects,
         )
         .await;
 
-        let mut game = game::Game::new();
-
         tokio::select! {
             _ = async {
-                lobby::run(&mut data, &channels, connect_enabled.clone()).await;
-                game::run(&mut game, &data, &channels).await;
+                let rules = lobby::run(
+                    &mut data,
+                    &channels,
+                    connect_enabled.clone(),
+                    self.config.rules.rules(),
+                    self.config.fill_seats_to,
+                )
+                .await;
+
+                // bots take whatever seats are left
+                let mut bots = bots::Bots::new();
+                let humans = data.lock().player_count();
+                let fill_to = self.config.fill_seats_to.min(config::MAX_PLAYER_COUNT);
+                for _ in humans..fill_to {
+                    bots.add(self.config.bot_strategy, rand::random());
+                }
+
+                let grace = Duration::from_secs(self.config.reconnect_grace_secs);
+                let mut absent = absent::Absent::new(grace, self.config.bot_strategy);
+
+                let mut game = game::Game::new(rules, self.config.timings());
+                let log = self.config.game_log.as_deref().and_then(game::open_log);
+                game::run(&mut game, &mut bots, &mut absent, log, &data, &channels).await;
             } => {}
-            _ = token.cancelled() => {
-                let _ = game;
-            }
+            _ = token.cancelled() => {}
         }
 
         channels.broadcast_event(server::Event::ServerClosing).await;
@@ -6,16 +6,25 @@ use std::{
     },
 };
 
-use common::event::client;
+use common::{
+    event::{client, server},
+    rules::RuleSet,
+};
 use tracing::{info, warn};
 
 use crate::{channels::Connection, config, Channels, GameData};
 
+/// Wait in the lobby until the host starts the game.
+///
+/// Bots will fill the table up to `fill_seats_to` players, so fewer people are needed to start.
+/// Returns the house rules chosen by the host.
 pub async fn run(
     game_data: &mut GameData,
     channels: &Channels,
     connect_enabled: Arc<AtomicBool>,
-) {
+    mut rules: RuleSet,
+    fill_seats_to: usize,
+) -> RuleSet {
     connect_enabled.store(true, Ordering::Relaxed);
 
     let mut client_events = channels.incoming();
@@ -29,12 +38,14 @@ pub async fn run(
         );
 
         'interrupt: loop {
-            let can_start = game_data.lock().player_count() >= config::MIN_PLAYER_COUNT;
+            let seats = game_data.lock().player_count().max(fill_seats_to);
+            let can_start = seats >= config::MIN_PLAYER_COUNT;
 
             tokio::select! {
-                // request to start game
-                Ok((id, event)) = client_events.recv(), if can_start => {
-                    if try_start_game(id, event, game_data).is_break() {
+                // request to pick the rules or start game
+                Ok((id, event)) = client_events.recv() => {
+                    let flow = handle_host_event(id, event, game_data, channels, can_start, &mut rules).await;
+                    if flow.is_break() {
                         break 'waiting
                     }
                 }
@@ -59,16 +70,37 @@ pub async fn run(
     }
 
     connect_enabled.store(false, Ordering::Relaxed);
+
+    rules
 }
 
-fn try_start_game(id: uuid::Uuid, event: client::Event, data: &GameData) -> ControlFlow<()> {
-    if let client::Event::Start = event {
-        if host_id(data).is_some_and(|host| host == id) {
+async fn handle_host_event(
+    id: uuid::Uuid,
+    event: client::Event,
+    data: &GameData,
+    channels: &Channels,
+    can_start: bool,
+    rules: &mut RuleSet,
+) -> ControlFlow<()> {
+    let is_host = host_id(data).is_some_and(|host| host == id);
+
+    match event {
+        client::Event::Start | client::Event::ChooseRules(..) if !is_host => {}
+        client::Event::Start if can_start => {
             info!("host started game");
             return ControlFlow::Break(());
         }
-    } else {
-        warn!("player {id} in lobby gave another event {event:?} when expecting `Event::Start`");
+        client::Event::Start => {}
+        client::Event::ChooseRules(preset) => {
+            info!("host chose the {preset:?} rules");
+            *rules = preset.rules();
+            channels
+                .broadcast_event(server::Event::Rules(rules.clone()))
+                .await;
+        }
+        event => {
+            warn!("player {id} in lobby gave another event {event:?} when expecting `Event::Start` or `Event::ChooseRules`");
+        }
     }
 
     ControlFlow::Continue(())
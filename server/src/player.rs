@@ -1,25 +1,68 @@
-use std::ops::ControlFlow;
+use std::{future, io, ops::ControlFlow};
 
-use common::event::{client, server};
+use common::event::{client, server, Heartbeat};
 use common::stream;
-use futures::SinkExt;
-use tokio::net::TcpStream;
+use futures::{SinkExt, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
-use tracing::error;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_util::bytes::{Bytes, BytesMut};
+use tracing::{error, warn};
 
 use crate::{Channels, GameData};
 
 pub struct PlayerConn {
     pub read: stream::Read<client::Event>,
     pub write: stream::Write<server::Event>,
+    /// How to check the client is still there, if they answer pings.
+    pub heartbeat: Option<Heartbeat>,
 }
 
 impl PlayerConn {
-    pub fn from(socket: TcpStream) -> Self {
+    pub fn from<S>(socket: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
         let (read, write) = stream::split(socket);
-        Self { read, write }
+        Self {
+            read,
+            write,
+            heartbeat: None,
+        }
+    }
+
+    /// Upgrade the `socket` to a WebSocket, one event per message.
+    ///
+    /// Events are sent as binary messages, either text or binary are read.
+    pub async fn websocket<S>(socket: S) -> Result<Self, tungstenite::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let websocket = tokio_tungstenite::accept_async(socket).await?;
+        let (sink, messages) = futures::StreamExt::split(websocket);
+
+        let frames = messages
+            .map_err(io::Error::other)
+            .try_filter_map(|message| {
+                future::ready(Ok(match message {
+                    Message::Binary(bytes) => Some(BytesMut::from(&bytes[..])),
+                    Message::Text(text) => Some(BytesMut::from(text.as_bytes())),
+                    // pings are answered for us, and the stream ends after a close
+                    _ => None,
+                }))
+            });
+        let sink = sink
+            .sink_map_err(io::Error::other)
+            .with(|frame: Bytes| future::ready(Ok(Message::Binary(frame))));
+
+        Ok(Self {
+            read: stream::Read::new(frames),
+            write: stream::Write::new(sink),
+            heartbeat: None,
+        })
     }
 }
 
@@ -27,26 +70,50 @@ pub async fn spawn(
     data: GameData,
     channels: &Channels,
     id: uuid::Uuid,
+    connection: uuid::Uuid,
     mut conn: PlayerConn,
 ) -> oneshot::Receiver<()> {
     const EVENT_CAPACITY: usize = 32;
 
     let (tx, rx) = mpsc::channel(EVENT_CAPACITY);
 
-    let events = channels.register(id, tx).await;
+    let events = channels.register(id, connection, tx).await;
 
     // turn channels into streams
     let mut server_events = Box::pin(ReceiverStream::new(rx));
 
     let (closing_rx, closing_tx) = oneshot::channel();
 
+    let mut pings = conn.heartbeat.map(|heartbeat| {
+        let mut pings = time::interval_at(Instant::now() + heartbeat.interval, heartbeat.interval);
+        pings.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        pings
+    });
+    let misses = conn.heartbeat.map_or(0, |heartbeat| heartbeat.misses);
+
     tokio::spawn(async move {
+        // pings gone by without hearing from the client
+        let mut missed = 0;
+
         loop {
             tokio::select! {
+                _ = tick(pings.as_mut()) => {
+                    if missed >= misses {
+                        warn!("client ({id}) stopped responding");
+                        break;
+                    }
+                    missed += 1;
+                    if conn.write.send(server::Event::Ping).await.is_err() {
+                        break;
+                    }
+                }
                 res = server_events.next() => {
                     match res {
                         Some(event) => {
-                            conn.write.send(event).await.expect("failed to send event");
+                            if let Err(e) = conn.write.send(event).await {
+                                error!("failed to send event to client ({id}): `{e}`");
+                                break;
+                            }
                         }
                         None => {
                             break;
@@ -54,6 +121,7 @@ pub async fn spawn(
                     }
                 }
                 res = conn.read.next() => {
+                    missed = 0;
                     match res {
                         Some(Ok(event)) => {
                             match try_handle_early(&data, &mut conn, event).await {
@@ -85,6 +153,16 @@ pub async fn spawn(
     closing_tx
 }
 
+/// The next tick of `interval`, if there is one.
+async fn tick(interval: Option<&mut Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => future::pending().await,
+    }
+}
+
 async fn try_handle_early(
     data: &GameData,
     conn: &mut PlayerConn,
@@ -92,6 +170,8 @@ async fn try_handle_early(
 ) -> Result<ControlFlow<()>, client::Event> {
     let res = match event {
         client::Event::Leave => ControlFlow::Break(()),
+        // hearing anything back is enough
+        client::Event::Pong => ControlFlow::Continue(()),
         client::Event::GetLobbyInfo => {
             let player_count = data.lock().player_count();
 
@@ -107,3 +187,83 @@ async fn try_handle_early(
 
     Ok(res)
 }
+
+#[tokio::test]
+async fn websocket_messages_are_events() {
+    use common::event::Capability;
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let browser = tokio::spawn(async move {
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}"))
+            .await
+            .unwrap();
+        let hello = r#"{"Hello":{"protocol_version":3,"client_name":"browser","capabilities":[]}}"#;
+        ws.send(Message::text(hello)).await.unwrap();
+        ws.next().await.unwrap().unwrap()
+    });
+
+    let (socket, _) = listener.accept().await.unwrap();
+    let mut conn = PlayerConn::websocket(socket).await.unwrap();
+
+    let event = conn.read.next().await.unwrap().unwrap();
+    assert!(matches!(event, client::Event::Hello { client_name, .. } if client_name == "browser"));
+
+    let hello = server::Event::Hello {
+        protocol_version: 3,
+        capabilities: vec![Capability::Rejoin],
+        format: stream::Format::Json,
+        heartbeat: None,
+    };
+    conn.write.send(hello).await.unwrap();
+
+    let Message::Binary(bytes) = browser.await.unwrap() else {
+        panic!("events should be sent as binary messages");
+    };
+    let reply = stream::Format::Json
+        .decode::<server::Event>(&bytes)
+        .unwrap();
+    assert!(matches!(
+        reply,
+        server::Event::Hello {
+            protocol_version: 3,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn silent_clients_are_disconnected() {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::net::{TcpListener, TcpStream};
+
+    use crate::channels;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    // connects, but never answers
+    let _silent = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (socket, _) = listener.accept().await.unwrap();
+
+    let heartbeat = Heartbeat {
+        interval: Duration::from_millis(20),
+        misses: 2,
+    };
+    let mut conn = PlayerConn::from(socket);
+    conn.heartbeat = Some(heartbeat);
+
+    let channels = Arc::new(channels::Channels::start());
+    let start = Instant::now();
+    let (id, connection) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+    let left = spawn(GameData::default(), &channels, id, connection, conn).await;
+
+    time::timeout(Duration::from_secs(5), left)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(start.elapsed() >= heartbeat.limit());
+}
@@ -0,0 +1,94 @@
+//! Securing connections with TLS.
+
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use tokio_rustls::{
+    rustls::{
+        self,
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    },
+    TlsAcceptor,
+};
+
+use crate::config::TlsConfig;
+
+/// Accepts connections with the certificate and key the `config` points to.
+pub fn acceptor(config: &TlsConfig) -> anyhow::Result<TlsAcceptor> {
+    let certs = CertificateDer::pem_file_iter(&config.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read certificates from {}", config.cert.display()))?;
+    let key = PrivateKeyDer::from_pem_file(&config.key)
+        .with_context(|| format!("failed to read private key from {}", config.key.display()))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("invalid certificate or key")?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+#[tokio::test]
+async fn only_clients_trusting_the_certificate_connect() {
+    use common::{event::client, stream};
+    use futures::{SinkExt, StreamExt};
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use crate::player::PlayerConn;
+
+    // a certificate authority, and the certificate it signed for the server
+    let ca_key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(Vec::new()).unwrap();
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = params.self_signed(&ca_key).unwrap();
+
+    let key = KeyPair::generate().unwrap();
+    let params = CertificateParams::new(vec!["localhost".into()]).unwrap();
+    let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("cambio-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |name, pem: String| {
+        let path = dir.join(name);
+        std::fs::write(&path, pem).unwrap();
+        path
+    };
+    let config = TlsConfig {
+        cert: write("cert.pem", cert.pem()),
+        key: write("key.pem", key.serialize_pem()),
+    };
+    let ca = write("ca.pem", ca.pem());
+
+    let acceptor = acceptor(&config).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(acceptor.accept(stream).await.is_err());
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut conn = PlayerConn::from(acceptor.accept(stream).await.unwrap());
+        conn.read.next().await.unwrap().unwrap()
+    });
+
+    // only trusts the system's certificate authorities
+    let untrusted = ::client::Tls::new("localhost", None).unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    assert!(untrusted.connect(stream).await.is_err());
+
+    let tls = ::client::Tls::new("localhost", Some(&ca)).unwrap();
+    let stream = tls
+        .connect(TcpStream::connect(addr).await.unwrap())
+        .await
+        .unwrap();
+    let (_, mut write) = stream::split::<client::Event, client::Event, _>(stream);
+    write.send(client::Event::Leave).await.unwrap();
+
+    assert!(matches!(server.await.unwrap(), client::Event::Leave));
+    std::fs::remove_dir_all(dir).unwrap();
+}